@@ -18,16 +18,44 @@ use winit::window::Window;
 #[derive(Clone)]
 pub struct RenderPasses {
 	text: Arc<RenderPass>,
+	labels: Arc<RenderPass>,
+	layers: Arc<RenderPass>,
 	screen: Arc<RenderPass>,
 }
 impl RenderPasses {
 	fn new(device: &Arc<Device>, format: Format) -> Self {
+		// windings are signed, and each channel counts two samples, so they need more than 8 bits
 		let text = Arc::new(
 			vulkano::single_pass_renderpass!(device.clone(),
 				attachments: {
-					winding: { load: Clear, store: Store, format: Format::R8G8B8A8Unorm, samples: 1, }
+					winding: { load: Clear, store: Store, format: Format::R16G16B16A16Sfloat, samples: 1, },
+					color: { load: Clear, store: Store, format: Format::R8G8B8A8Unorm, samples: 1, }
 				},
-				pass: { color: [winding], depth_stencil: {} }
+				pass: { color: [winding, color], depth_stencil: {} }
+			)
+			.unwrap(),
+		);
+
+		// world-space labels also keep their depth, to test it against the scene's when they're composited
+		let labels = Arc::new(
+			vulkano::single_pass_renderpass!(device.clone(),
+				attachments: {
+					winding: { load: Clear, store: Store, format: Format::R16G16B16A16Sfloat, samples: 1, },
+					color: { load: Clear, store: Store, format: Format::R8G8B8A8Unorm, samples: 1, },
+					depth: { load: Clear, store: Store, format: Format::R32Sfloat, samples: 1, }
+				},
+				pass: { color: [winding, color, depth], depth_stencil: {} }
+			)
+			.unwrap(),
+		);
+
+		// color glyph layers are composited into this one at a time, so it's cleared once per frame instead of per pass
+		let layers = Arc::new(
+			vulkano::single_pass_renderpass!(device.clone(),
+				attachments: {
+					color: { load: Load, store: Store, format: Format::R8G8B8A8Unorm, samples: 1, }
+				},
+				pass: { color: [color], depth_stencil: {} }
 			)
 			.unwrap(),
 		);
@@ -43,7 +71,7 @@ impl RenderPasses {
 			.unwrap(),
 		);
 
-		Self { text, screen }
+		Self { text, labels, layers, screen }
 	}
 }
 
@@ -97,6 +125,19 @@ impl Gfx {
 			let fs_textq = fs_textq::Shader::load(device.clone()).unwrap();
 			let vs_sprite = vs_sprite::Shader::load(device.clone()).unwrap();
 			let fs_text2 = fs_text2::Shader::load(device.clone()).unwrap();
+			let vs_rect = vs_rect::Shader::load(device.clone()).unwrap();
+			let fs_rect = fs_rect::Shader::load(device.clone()).unwrap();
+			let vs_label = vs_label::Shader::load(device.clone()).unwrap();
+			let fs_label = fs_label::Shader::load(device.clone()).unwrap();
+			let fs_labelq = fs_labelq::Shader::load(device.clone()).unwrap();
+			let fs_label2 = fs_label2::Shader::load(device.clone()).unwrap();
+			let fs_layer = fs_layer::Shader::load(device.clone()).unwrap();
+			let fs_image = fs_image::Shader::load(device.clone()).unwrap();
+			let vs_bitmap = vs_bitmap::Shader::load(device.clone()).unwrap();
+			let fs_bitmap = fs_bitmap::Shader::load(device.clone()).unwrap();
+			let fs_sdf = fs_sdf::Shader::load(device.clone()).unwrap();
+			let vs_label_bitmap = vs_label_bitmap::Shader::load(device.clone()).unwrap();
+			let fs_effects = fs_effects::Shader::load(device.clone()).unwrap();
 
 			self.devices.push(Arc::new(DeviceCtx {
 				device,
@@ -108,6 +149,19 @@ impl Gfx {
 				fs_text,
 				vs_sprite,
 				fs_text2,
+				vs_rect,
+				fs_rect,
+				vs_label,
+				fs_label,
+				fs_labelq,
+				fs_label2,
+				fs_layer,
+				fs_image,
+				vs_bitmap,
+				fs_bitmap,
+				fs_sdf,
+				vs_label_bitmap,
+				fs_effects,
 			}));
 
 			self.devices.last().unwrap()
@@ -125,6 +179,19 @@ pub struct DeviceCtx {
 	fs_textq: fs_textq::Shader,
 	vs_sprite: vs_sprite::Shader,
 	fs_text2: fs_text2::Shader,
+	vs_rect: vs_rect::Shader,
+	fs_rect: fs_rect::Shader,
+	vs_label: vs_label::Shader,
+	fs_label: fs_label::Shader,
+	fs_labelq: fs_labelq::Shader,
+	fs_label2: fs_label2::Shader,
+	fs_layer: fs_layer::Shader,
+	fs_image: fs_image::Shader,
+	vs_bitmap: vs_bitmap::Shader,
+	fs_bitmap: fs_bitmap::Shader,
+	fs_sdf: fs_sdf::Shader,
+	vs_label_bitmap: vs_label_bitmap::Shader,
+	fs_effects: fs_effects::Shader,
 }
 impl DeviceCtx {
 	pub fn device(&self) -> &Arc<Device> {
@@ -163,6 +230,58 @@ impl DeviceCtx {
 		&self.fs_text2
 	}
 
+	pub fn vs_rect(&self) -> &vs_rect::Shader {
+		&self.vs_rect
+	}
+
+	pub fn fs_rect(&self) -> &fs_rect::Shader {
+		&self.fs_rect
+	}
+
+	pub fn vs_label(&self) -> &vs_label::Shader {
+		&self.vs_label
+	}
+
+	pub fn fs_label(&self) -> &fs_label::Shader {
+		&self.fs_label
+	}
+
+	pub fn fs_labelq(&self) -> &fs_labelq::Shader {
+		&self.fs_labelq
+	}
+
+	pub fn fs_label2(&self) -> &fs_label2::Shader {
+		&self.fs_label2
+	}
+
+	pub fn fs_layer(&self) -> &fs_layer::Shader {
+		&self.fs_layer
+	}
+
+	pub fn fs_image(&self) -> &fs_image::Shader {
+		&self.fs_image
+	}
+
+	pub fn vs_bitmap(&self) -> &vs_bitmap::Shader {
+		&self.vs_bitmap
+	}
+
+	pub fn fs_bitmap(&self) -> &fs_bitmap::Shader {
+		&self.fs_bitmap
+	}
+
+	pub fn fs_sdf(&self) -> &fs_sdf::Shader {
+		&self.fs_sdf
+	}
+
+	pub fn vs_label_bitmap(&self) -> &vs_label_bitmap::Shader {
+		&self.vs_label_bitmap
+	}
+
+	pub fn fs_effects(&self) -> &fs_effects::Shader {
+		&self.fs_effects
+	}
+
 	pub fn physical_device(&self) -> PhysicalDevice {
 		self.device.physical_device()
 	}
@@ -194,3 +313,46 @@ pub mod vs_sprite {
 pub mod fs_text2 {
 	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/text2_frag.glsl" }
 }
+pub mod vs_rect {
+	vulkano_shaders::shader! { ty: "vertex", path: "src/gfx/render/rect_vert.glsl" }
+}
+pub mod fs_rect {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/rect_frag.glsl" }
+}
+pub mod vs_label {
+	vulkano_shaders::shader! { ty: "vertex", path: "src/gfx/render/label_vert.glsl" }
+}
+pub mod fs_label {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/text_frag.glsl", define: [("LABELS", "1")] }
+}
+pub mod fs_labelq {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/textq_frag.glsl", define: [("LABELS", "1")] }
+}
+pub mod fs_label2 {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/text2_frag.glsl", define: [("LABELS", "1")] }
+}
+pub mod fs_layer {
+	vulkano_shaders::shader! {
+		ty: "fragment",
+		path: "src/gfx/render/text2_frag.glsl",
+		define: [("COLOR_LAYERS", "1")]
+	}
+}
+pub mod fs_image {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/image_frag.glsl" }
+}
+pub mod vs_bitmap {
+	vulkano_shaders::shader! { ty: "vertex", path: "src/gfx/render/bitmap_vert.glsl" }
+}
+pub mod fs_bitmap {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/bitmap_frag.glsl" }
+}
+pub mod fs_sdf {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/sdf_frag.glsl" }
+}
+pub mod vs_label_bitmap {
+	vulkano_shaders::shader! { ty: "vertex", path: "src/gfx/render/bitmap_vert.glsl", define: [("LABELS", "1")] }
+}
+pub mod fs_effects {
+	vulkano_shaders::shader! { ty: "fragment", path: "src/gfx/render/effects_frag.glsl" }
+}
@@ -3,34 +3,34 @@ use crate::{
 	gfx::{
 		camera::Camera,
 		model::Mesh,
-		text::{ChInstance, Font},
+		text::{field::TextField, BitmapDraw, BitmapInstance, ChInstance, Fonts, Label, Text, TextDraw},
 		window::Window,
 		Gfx, RenderPasses,
 	},
 };
+use nalgebra::Vector2;
 use shipyard::{IntoIter, UniqueViewMut, View, ViewMut};
-use std::{iter, sync::Arc};
+use std::{collections::HashMap, iter, sync::Arc};
 use vulkano::{
-	buffer::{cpu_pool::CpuBufferPoolChunk, BufferUsage, ImmutableBuffer, TypedBufferAccess},
+	buffer::{BufferUsage, ImmutableBuffer},
 	command_buffer::{
-		AutoCommandBufferBuilder, CommandBufferUsage, DrawIndexedIndirectCommand, DrawIndirectCommand, DynamicState,
-		SubpassContents,
+		AutoCommandBufferBuilder, CommandBufferUsage, DynamicState, PrimaryAutoCommandBuffer, SubpassContents,
 	},
 	descriptor::{
 		descriptor_set::{PersistentDescriptorSet, UnsafeDescriptorSetLayout},
 		DescriptorSet,
 	},
-	format::Format,
-	image::{attachment::AttachmentImage, view::ImageView},
-	memory::pool::StdMemoryPool,
+	format::{ClearValue, Format},
+	image::{attachment::AttachmentImage, view::ImageView, ImageUsage},
 	pipeline::{
 		blend::{AttachmentBlend, BlendFactor, BlendOp},
+		depth_stencil::DepthStencil,
 		vertex::{OneVertexOneInstanceDefinition, TwoBuffersDefinition},
-		viewport::Viewport,
+		viewport::{Scissor, Viewport},
 		GraphicsPipeline, GraphicsPipelineAbstract,
 	},
 	render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
-	sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+	sampler::{BorderColor, Filter, MipmapMode, Sampler, SamplerAddressMode},
 	swapchain,
 	swapchain::AcquireError,
 	sync,
@@ -38,20 +38,40 @@ use vulkano::{
 };
 
 pub struct RenderWindowState {
-	font: Font,
 	previous_frame_end: Option<Box<dyn GpuFuture + Send + Sync>>,
 	text_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+	label_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+	layer_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+	layer_image: Arc<AttachmentImage>,
 	framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
 	pipeline_3d: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
 	pipeline_text: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
 	pipeline_textq: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
 	pipeline_text2: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_rect: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_label: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_labelq: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_label2: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_layer: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_image: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_bitmap: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_sdf: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_label_bitmap: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_label_sdf: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+	pipeline_effects: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
 	recreate_swapchain: bool,
-	font_cmds: Arc<dyn TypedBufferAccess<Content = [DrawIndexedIndirectCommand]> + Send + Sync>,
-	font_qcmds: Arc<dyn TypedBufferAccess<Content = [DrawIndirectCommand]> + Send + Sync>,
-	font_instances: Arc<CpuBufferPoolChunk<ChInstance, Arc<StdMemoryPool>>>,
 	triangle: Arc<ImmutableBuffer<[TriangleVertex]>>,
+	quad: Arc<ImmutableBuffer<[TriangleVertex]>>,
 	text2_set: Arc<dyn DescriptorSet + Send + Sync>,
+	label2_set: Arc<dyn DescriptorSet + Send + Sync>,
+	layer_set: Arc<dyn DescriptorSet + Send + Sync>,
+	image_set: Arc<dyn DescriptorSet + Send + Sync>,
+	effects_set: Arc<dyn DescriptorSet + Send + Sync>,
+	// atlases are loaded along with fonts, so their sets are made the first time they're drawn, and kept for as long
+	// as they're drawn every frame. they're keyed by the atlas' address, which the set keeps from being reused.
+	bitmap_layout: Arc<UnsafeDescriptorSetLayout>,
+	bitmap_sampler: Arc<Sampler>,
+	bitmap_sets: HashMap<usize, Arc<dyn DescriptorSet + Send + Sync>>,
 }
 impl RenderWindowState {
 	pub fn new(gfx: &mut Gfx, window: &Window) -> Self {
@@ -61,26 +81,42 @@ impl RenderWindowState {
 		let queue = device_ctx.queue();
 		let render_pass = create_render_pass(gfx, window);
 
-		let text_image = ImageView::new(
-			AttachmentImage::sampled_input_attachment(
-				device.clone(),
-				window.images()[0].dimensions(),
-				Format::R8G8B8A8Unorm,
-			)
-			.unwrap(),
+		let text_image = create_text_image(window, Format::R16G16B16A16Sfloat);
+		let text_color_image = create_text_image(window, Format::R8G8B8A8Unorm);
+		let label_image = create_text_image(window, Format::R16G16B16A16Sfloat);
+		let label_color_image = create_text_image(window, Format::R8G8B8A8Unorm);
+		let label_depth_image = create_text_image(window, Format::R32Sfloat);
+		let layer_usage =
+			ImageUsage { transfer_destination: true, sampled: true, color_attachment: true, ..ImageUsage::none() };
+		let layer_image = AttachmentImage::with_usage(
+			device.clone(),
+			window.images()[0].dimensions(),
+			Format::R8G8B8A8Unorm,
+			layer_usage,
 		)
 		.unwrap();
+		let layer_view = ImageView::new(layer_image.clone()).unwrap();
 
-		let text_framebuffer = create_text_framebuffer(&render_pass.text, &text_image);
+		let text_framebuffer = create_text_framebuffer(&render_pass.text, &text_image, &text_color_image);
+		let label_framebuffer =
+			create_label_framebuffer(&render_pass.labels, &label_image, &label_color_image, &label_depth_image);
+		let layer_framebuffer = create_layer_framebuffer(&render_pass.layers, &layer_view);
 		let framebuffers = create_framebuffers(window, &render_pass.screen);
 		let pipeline_3d = create_3d_pipeline(window, &render_pass.screen);
 		let pipeline_text = create_text_pipeline(window, &render_pass.text);
 		let pipeline_textq = create_textq_pipeline(window, &render_pass.text);
 		let (pipeline_text2, pipeline_text2_layout) = create_text2_pipeline(window, &render_pass.screen);
-
-		let mut font = Font::new(device, 16.0);
-		let verts_future = font.load_block(queue, "Basic Latin");
-		let (font_cmds, font_qcmds, font_instances) = font.draw("The quick brown fox jumps over the lazy dog.");
+		let pipeline_rect = create_rect_pipeline(window, &render_pass.screen);
+		let pipeline_label = create_label_pipeline(window, &render_pass.labels);
+		let pipeline_labelq = create_labelq_pipeline(window, &render_pass.labels);
+		let (pipeline_label2, pipeline_label2_layout) = create_label2_pipeline(window, &render_pass.screen);
+		let (pipeline_layer, pipeline_layer_layout) = create_layer_pipeline(window, &render_pass.layers);
+		let (pipeline_image, pipeline_image_layout) = create_image_pipeline(window, &render_pass.screen);
+		let (pipeline_bitmap, bitmap_layout) = create_bitmap_pipeline(window, &render_pass.layers);
+		let pipeline_sdf = create_sdf_pipeline(window, &render_pass.layers);
+		let pipeline_label_bitmap = create_label_bitmap_pipeline(window, &render_pass.screen);
+		let pipeline_label_sdf = create_label_sdf_pipeline(window, &render_pass.screen);
+		let (pipeline_effects, pipeline_effects_layout) = create_effects_pipeline(window, &render_pass.layers);
 
 		let triangle: Vec<TriangleVertex> =
 			vec![TriangleVertex { v_pos: [-1.0, -1.0] }, TriangleVertex { v_pos: [3.0, -1.0] }, TriangleVertex {
@@ -89,6 +125,11 @@ impl RenderWindowState {
 		let (triangle, triangle_future) =
 			ImmutableBuffer::from_iter(triangle.into_iter(), BufferUsage::vertex_buffer(), queue.clone()).unwrap();
 
+		let quad = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]];
+		let quad = quad.iter().map(|&v_pos| TriangleVertex { v_pos });
+		let (quad, quad_future) =
+			ImmutableBuffer::from_iter(quad, BufferUsage::vertex_buffer(), queue.clone()).unwrap();
+
 		let sampler = Sampler::new(
 			device.clone(),
 			Filter::Nearest,
@@ -103,31 +144,115 @@ impl RenderWindowState {
 			0.0,
 		)
 		.unwrap();
+		// glyph images are scaled to the text's size, so they're filtered
+		let bitmap_sampler = Sampler::new(
+			device.clone(),
+			Filter::Linear,
+			Filter::Linear,
+			MipmapMode::Nearest,
+			SamplerAddressMode::ClampToEdge,
+			SamplerAddressMode::ClampToEdge,
+			SamplerAddressMode::ClampToEdge,
+			0.0,
+			1.0,
+			0.0,
+			0.0,
+		)
+		.unwrap();
 		let text2_set = Arc::new(
 			PersistentDescriptorSet::start(pipeline_text2_layout)
-				.add_sampled_image(text_image, sampler)
+				.add_sampled_image(text_image.clone(), sampler.clone())
+				.unwrap()
+				.add_sampled_image(text_color_image.clone(), sampler.clone())
+				.unwrap()
+				.build()
+				.unwrap(),
+		);
+		// effects sample around each pixel, and shouldn't pick up text from the other side of the window
+		let effects_sampler = Sampler::new(
+			device.clone(),
+			Filter::Nearest,
+			Filter::Nearest,
+			MipmapMode::Nearest,
+			SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+			SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+			SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+			0.0,
+			1.0,
+			0.0,
+			0.0,
+		)
+		.unwrap();
+		let effects_set = Arc::new(
+			PersistentDescriptorSet::start(pipeline_effects_layout)
+				.add_sampled_image(text_image.clone(), effects_sampler)
+				.unwrap()
+				.build()
+				.unwrap(),
+		);
+		let layer_set = Arc::new(
+			PersistentDescriptorSet::start(pipeline_layer_layout)
+				.add_sampled_image(text_image, sampler.clone())
+				.unwrap()
+				.add_sampled_image(text_color_image, sampler.clone())
+				.unwrap()
+				.build()
+				.unwrap(),
+		);
+		let image_set = Arc::new(
+			PersistentDescriptorSet::start(pipeline_image_layout)
+				.add_sampled_image(layer_view, sampler.clone())
+				.unwrap()
+				.build()
+				.unwrap(),
+		);
+		let label2_set = Arc::new(
+			PersistentDescriptorSet::start(pipeline_label2_layout)
+				.add_sampled_image(label_image, sampler.clone())
+				.unwrap()
+				.add_sampled_image(label_color_image, sampler.clone())
+				.unwrap()
+				.add_sampled_image(label_depth_image, sampler)
 				.unwrap()
 				.build()
 				.unwrap(),
 		);
 
-		verts_future.join(triangle_future).then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+		triangle_future.join(quad_future).then_signal_fence_and_flush().unwrap().wait(None).unwrap();
 
 		Self {
-			font,
 			previous_frame_end: Some(Box::new(sync::now(device.clone()))),
 			text_framebuffer,
+			label_framebuffer,
+			layer_framebuffer,
+			layer_image,
 			framebuffers,
 			pipeline_3d,
 			pipeline_text,
 			pipeline_textq,
 			pipeline_text2,
+			pipeline_rect,
+			pipeline_label,
+			pipeline_labelq,
+			pipeline_label2,
+			pipeline_layer,
+			pipeline_image,
+			pipeline_bitmap,
+			pipeline_sdf,
+			pipeline_label_bitmap,
+			pipeline_label_sdf,
+			pipeline_effects,
 			recreate_swapchain: false,
-			font_cmds,
-			font_qcmds,
-			font_instances,
 			triangle,
+			quad,
 			text2_set,
+			label2_set,
+			layer_set,
+			image_set,
+			effects_set,
+			bitmap_layout,
+			bitmap_sampler,
+			bitmap_sets: HashMap::new(),
 		}
 	}
 
@@ -138,11 +263,14 @@ impl RenderWindowState {
 
 pub fn render(
 	mut gfx: UniqueViewMut<Gfx>,
+	mut fonts: UniqueViewMut<Fonts>,
 	mut windows: ViewMut<Window>,
 	mut states: ViewMut<RenderWindowState>,
 	cameras: ViewMut<Camera>,
 	meshes: View<Mesh>,
-	strings: View<&'static str>,
+	mut texts: ViewMut<Text>,
+	fields: View<TextField>,
+	mut labels: ViewMut<Label>,
 ) {
 	for (mut window, mut state, camera) in (&mut windows, &mut states, &cameras).iter() {
 		state.previous_frame_end.as_mut().unwrap().cleanup_finished();
@@ -170,42 +298,266 @@ pub fn render(
 			state.recreate_swapchain = true;
 		}
 
+		if let Some(font_future) = fonts.update() {
+			let previous_frame_end = state.previous_frame_end.take().unwrap();
+			state.previous_frame_end = Some(Box::new(previous_frame_end.join(font_future)));
+		}
+
 		let mut builder =
 			AutoCommandBufferBuilder::primary(device.clone(), queue.family(), CommandBufferUsage::OneTimeSubmit)
 				.unwrap();
-		builder.begin_render_pass(state.text_framebuffer.clone(), SubpassContents::Inline, vec![[0.0].into()]).unwrap();
 
-		let pc = crate::gfx::vs_text::ty::PushConstant {
-			pos: [-0.9, -0.8],
-			target_size: [window.inner_size().width as f32, window.inner_size().height as f32],
-			scale: state.font.scale,
+		let target_size = Vector2::new(window.inner_size().width as f32, window.inner_size().height as f32);
+		let to_clip = |pos: Vector2<f32>| pos.component_div(&target_size) * 2.0 - Vector2::repeat(1.0);
+		let rect = |(min, max): (Vector2<f32>, Vector2<f32>), color| crate::gfx::vs_rect::ty::PushConstant {
+			color,
+			pos: to_clip(min).into(),
+			size: ((max - min).component_div(&target_size) * 2.0).into(),
 		};
-		let block_info = &state.font.block_info["Basic Latin"];
-		for string in strings.iter() {
-			let (font_cmds, font_qcmds, font_instances) = state.font.draw(string);
+
+		// backgrounds go under the text, and overlays on top of it
+		let mut backgrounds = vec![];
+		let mut overlays = vec![];
+		let mut draws = vec![];
+		let mut bitmap_sets = HashMap::new();
+		let mut effects = vec![];
+		for mut text in (&mut texts).iter() {
+			let layout = text.layout(&fonts);
+			let origin = fonts.snap(&layout, text.origin(&layout));
+			if let Some(color) = text.background {
+				let (min, max) = layout.bounds();
+				backgrounds.push(rect((origin + min, origin + max), color));
+			}
+			if text.stroke.is_some() || text.shadow.is_some() || text.glow.is_some() {
+				let (stroke_color, stroke_width) =
+					text.stroke.map_or(([0.0; 4], 0.0), |stroke| (stroke.color, stroke.width));
+				let (shadow_color, shadow_offset, shadow_blur) = text
+					.shadow
+					.map_or(([0.0; 4], Vector2::zeros(), 0.0), |shadow| (shadow.color, shadow.offset, shadow.blur));
+				let (glow_color, glow_radius) = text.glow.map_or(([0.0; 4], 0.0), |glow| (glow.color, glow.radius));
+				let pc = crate::gfx::fs_effects::ty::PushConstant {
+					stroke_color,
+					shadow_color,
+					glow_color,
+					shadow_offset: shadow_offset.into(),
+					target_size: target_size.into(),
+					stroke_width,
+					shadow_blur,
+					glow_radius,
+				};
+
+				// glyphs can reach a little past their lines, like accents and italics do
+				let (min, max) = layout.bounds();
+				let overshoot = Vector2::repeat(text.size / 2.0);
+				let (min, max) = (origin + min - overshoot, origin + max + overshoot);
+				let radius = Vector2::repeat(stroke_width.max(glow_radius));
+				let shadow = Vector2::repeat(shadow_blur);
+				let bounds = (
+					(min - radius).inf(&(min + shadow_offset - shadow)),
+					(max + radius).sup(&(max + shadow_offset + shadow)),
+				);
+				effects.push((draws.len(), pc, bounds));
+			}
+			draws.push((origin, fonts.draw_layout(&layout, true), fonts.draw_bitmaps(&layout)));
+		}
+		for field in fields.iter() {
+			backgrounds.extend(field.selection_rects().into_iter().map(|bounds| rect(bounds, field.selection_color)));
+			if field.focused {
+				overlays.push(rect(field.caret_rect(), field.caret_color));
+			}
+			for (origin, layout) in field.layouts() {
+				let origin = fonts.snap(layout, origin);
+				draws.push((origin, fonts.draw_layout(layout, true), fonts.draw_bitmaps(layout)));
+			}
+		}
+
+		let draws: Vec<_> = draws
+			.into_iter()
+			.map(|(origin, text_draws, bitmap_draws)| {
+				let pc = crate::gfx::vs_text::ty::PushConstant {
+					pos: to_clip(origin).into(),
+					target_size: target_size.into(),
+				};
+				(pc, text_draws, bitmap_draws)
+			})
+			.collect();
+
+		// effects go under everything else in the layer image. the effects shader grows and blurs the windings of
+		// everything within a text's effects, so texts whose effects overlap get separate winding passes, and the
+		// rest share one. each text's effects are only drawn within its own bounds.
+		builder.clear_color_image(state.layer_image.clone(), ClearValue::Float([0.0; 4])).unwrap();
+		let mut groups: Vec<Vec<_>> = vec![];
+		for effect in effects {
+			let overlaps = |&(_, _, (min, max)): &(usize, _, (Vector2<f32>, Vector2<f32>))| {
+				let (other_min, other_max) = effect.2;
+				min.x < other_max.x && other_min.x < max.x && min.y < other_max.y && other_min.y < max.y
+			};
+			match groups.iter_mut().find(|group| !group.iter().any(overlaps)) {
+				Some(group) => group.push(effect),
+				None => groups.push(vec![effect]),
+			}
+		}
+		for group in groups {
 			builder
-				.draw_indexed_indirect(
-					state.pipeline_text.clone(),
-					&DynamicState::none(),
-					vec![block_info.verts.clone(), font_instances.clone()],
-					block_info.indices.clone(),
-					font_cmds.clone(),
-					(),
-					pc,
-					vec![],
-				)
+				.begin_render_pass(state.text_framebuffer.clone(), SubpassContents::Inline, vec![
+					[0.0, 0.0, 0.0, 0.0].into(),
+					[0.0, 0.0, 0.0, 0.0].into(),
+				])
+				.unwrap();
+			for &(i, ..) in &group {
+				let (pc, text_draws, _) = &draws[i];
+				for draw in text_draws.iter().filter(|draw| draw.layer <= 1) {
+					draw_text(&mut builder, &state, draw, *pc);
+				}
+			}
+			builder.end_render_pass().unwrap();
+
+			builder
+				.begin_render_pass(state.layer_framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+				.unwrap();
+			for (_, effects_pc, (min, max)) in group {
+				let min = min.sup(&Vector2::zeros()).map(f32::floor);
+				let max = max.inf(&target_size).map(f32::ceil);
+				if min.x >= max.x || min.y >= max.y {
+					continue;
+				}
+				let scissor = Scissor {
+					origin: [min.x as i32, min.y as i32],
+					dimensions: [(max.x - min.x) as u32, (max.y - min.y) as u32],
+				};
+				builder
+					.draw(
+						state.pipeline_effects.clone(),
+						&DynamicState { scissors: Some(vec![scissor]), ..DynamicState::none() },
+						vec![state.triangle.clone()],
+						state.effects_set.clone(),
+						effects_pc,
+						vec![],
+					)
+					.unwrap();
+			}
+			builder.end_render_pass().unwrap();
+		}
+
+		// overlapping layers of color glyphs would cancel each other's windings out, so each layer gets its own text
+		// pass and is stacked on the ones below it. this happens before the main text pass, since they all share the
+		// text framebuffer.
+		let top_layer = draws.iter().flat_map(|(_, text_draws, _)| text_draws).map(|draw| draw.layer).max();
+		for layer in 1..=top_layer.unwrap_or(0) {
+			builder
+				.begin_render_pass(state.text_framebuffer.clone(), SubpassContents::Inline, vec![
+					[0.0, 0.0, 0.0, 0.0].into(),
+					[0.0, 0.0, 0.0, 0.0].into(),
+				])
+				.unwrap();
+			for (pc, text_draws, _) in &draws {
+				for draw in text_draws.iter().filter(|draw| draw.layer == layer) {
+					draw_text(&mut builder, &state, draw, *pc);
+				}
+			}
+			builder.end_render_pass().unwrap();
+
+			builder
+				.begin_render_pass(state.layer_framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
 				.unwrap()
-				.draw_indirect(
-					state.pipeline_textq.clone(),
+				.draw(
+					state.pipeline_layer.clone(),
 					&DynamicState::none(),
-					vec![block_info.qverts.clone(), font_instances.clone()],
-					font_qcmds.clone(),
+					vec![state.triangle.clone()],
+					state.layer_set.clone(),
 					(),
-					pc,
 					vec![],
 				)
+				.unwrap()
+				.end_render_pass()
 				.unwrap();
 		}
+
+		if draws.iter().any(|(_, _, bitmap_draws)| !bitmap_draws.is_empty()) {
+			builder
+				.begin_render_pass(state.layer_framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+				.unwrap();
+			for (pc, _, bitmap_draws) in &draws {
+				let pc = crate::gfx::vs_bitmap::ty::PushConstant { pos: pc.pos, target_size: pc.target_size };
+				for draw in bitmap_draws {
+					let pipeline = if draw.sdf { &state.pipeline_sdf } else { &state.pipeline_bitmap };
+					builder
+						.draw(
+							pipeline.clone(),
+							&DynamicState::none(),
+							vec![state.quad.clone(), draw.instances.clone()],
+							bitmap_set(&state, &mut bitmap_sets, draw),
+							pc,
+							vec![],
+						)
+						.unwrap();
+				}
+			}
+			builder.end_render_pass().unwrap();
+		}
+
+		builder
+			.begin_render_pass(state.text_framebuffer.clone(), SubpassContents::Inline, vec![
+				[0.0, 0.0, 0.0, 0.0].into(),
+				[0.0, 0.0, 0.0, 0.0].into(),
+			])
+			.unwrap();
+		for (pc, text_draws, _) in &draws {
+			for draw in text_draws.iter().filter(|draw| draw.layer == 0) {
+				draw_text(&mut builder, &state, draw, *pc);
+			}
+		}
+		builder.end_render_pass().unwrap();
+
+		builder
+			.begin_render_pass(state.label_framebuffer.clone(), SubpassContents::Inline, vec![
+				[0.0, 0.0, 0.0, 0.0].into(),
+				[0.0, 0.0, 0.0, 0.0].into(),
+				[1.0].into(),
+			])
+			.unwrap();
+		let camera_pos = camera.position();
+		let mut label_bitmaps = vec![];
+		for mut label in (&mut labels).iter() {
+			let layout = label.layout(&fonts);
+			let rotation = label.rotation.unwrap_or(*camera.rotation());
+			let pc = crate::gfx::vs_label::ty::PushConstant {
+				label_rot: (*rotation.as_vector()).into(),
+				label_pos: [label.position.x, label.position.y, label.position.z, 0.0],
+				camera_rot: (*camera.rotation().as_vector()).into(),
+				camera_pos: [camera_pos.x, camera_pos.y, camera_pos.z, 0.0],
+				camera_proj: (*camera.projection()).into(),
+				target_size: target_size.into(),
+				origin: label.origin(&layout).into(),
+			};
+			// labels only have one winding pass, so color glyphs are flattened to their bottom layer. glyph images and
+			// distance fields are drawn straight to the screen, since they don't need one.
+			label_bitmaps.push((pc, fonts.draw_bitmaps(&layout)));
+			for draw in fonts.draw_layout(&layout, false).into_iter().filter(|draw| draw.layer <= 1) {
+				builder
+					.draw_indexed_indirect(
+						state.pipeline_label.clone(),
+						&DynamicState::none(),
+						vec![draw.page.verts.clone(), draw.instances.clone()],
+						draw.page.indices.clone(),
+						draw.cmds.clone(),
+						(),
+						pc,
+						vec![],
+					)
+					.unwrap()
+					.draw_indirect(
+						state.pipeline_labelq.clone(),
+						&DynamicState::none(),
+						vec![draw.page.qverts.clone(), draw.instances.clone()],
+						draw.qcmds.clone(),
+						(),
+						pc,
+						vec![],
+					)
+					.unwrap();
+			}
+		}
 		builder.end_render_pass().unwrap();
 
 		builder
@@ -235,6 +587,57 @@ pub fn render(
 		}
 
 		builder
+			.draw(
+				state.pipeline_label2.clone(),
+				&DynamicState::none(),
+				vec![state.triangle.clone()],
+				state.label2_set.clone(),
+				(),
+				vec![],
+			)
+			.unwrap();
+
+		for (pc, bitmap_draws) in label_bitmaps {
+			let pc = crate::gfx::vs_label_bitmap::ty::PushConstant {
+				label_rot: pc.label_rot,
+				label_pos: pc.label_pos,
+				camera_rot: pc.camera_rot,
+				camera_pos: pc.camera_pos,
+				camera_proj: pc.camera_proj,
+				target_size: pc.target_size,
+				origin: pc.origin,
+			};
+			for draw in bitmap_draws {
+				let pipeline = if draw.sdf { &state.pipeline_label_sdf } else { &state.pipeline_label_bitmap };
+				builder
+					.draw(
+						pipeline.clone(),
+						&DynamicState::none(),
+						vec![state.quad.clone(), draw.instances.clone()],
+						bitmap_set(&state, &mut bitmap_sets, &draw),
+						pc,
+						vec![],
+					)
+					.unwrap();
+			}
+		}
+
+		for pc in backgrounds {
+			builder
+				.draw(state.pipeline_rect.clone(), &DynamicState::none(), vec![state.quad.clone()], (), pc, vec![])
+				.unwrap();
+		}
+
+		builder
+			.draw(
+				state.pipeline_image.clone(),
+				&DynamicState::none(),
+				vec![state.triangle.clone()],
+				state.image_set.clone(),
+				(),
+				vec![],
+			)
+			.unwrap()
 			.draw(
 				state.pipeline_text2.clone(),
 				&DynamicState::none(),
@@ -245,8 +648,16 @@ pub fn render(
 			)
 			.unwrap();
 
+		for pc in overlays {
+			builder
+				.draw(state.pipeline_rect.clone(), &DynamicState::none(), vec![state.quad.clone()], (), pc, vec![])
+				.unwrap();
+		}
+
 		builder.end_render_pass().unwrap();
 		let command_buffer = builder.build().unwrap();
+		// sets for atlases that weren't drawn this frame are dropped, along with the atlases fonts no longer hold
+		state.bitmap_sets = bitmap_sets;
 
 		let future = state
 			.previous_frame_end
@@ -272,15 +683,106 @@ pub fn render(
 	}
 }
 
+// the sets drawn this frame are gathered in `sets`, which replaces the state's once the frame is recorded
+fn bitmap_set(
+	state: &RenderWindowState,
+	sets: &mut HashMap<usize, Arc<dyn DescriptorSet + Send + Sync>>,
+	draw: &BitmapDraw,
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+	let key = Arc::as_ptr(&draw.atlas) as usize;
+	let set = sets.entry(key).or_insert_with(|| match state.bitmap_sets.get(&key) {
+		Some(set) => set.clone(),
+		None => Arc::new(
+			PersistentDescriptorSet::start(state.bitmap_layout.clone())
+				.add_sampled_image(draw.atlas.clone(), state.bitmap_sampler.clone())
+				.unwrap()
+				.build()
+				.unwrap(),
+		),
+	});
+	set.clone()
+}
+
+fn draw_text(
+	builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+	state: &RenderWindowState,
+	draw: &TextDraw,
+	pc: crate::gfx::vs_text::ty::PushConstant,
+) {
+	builder
+		.draw_indexed_indirect(
+			state.pipeline_text.clone(),
+			&DynamicState::none(),
+			vec![draw.page.verts.clone(), draw.instances.clone()],
+			draw.page.indices.clone(),
+			draw.cmds.clone(),
+			(),
+			pc,
+			vec![],
+		)
+		.unwrap()
+		.draw_indirect(
+			state.pipeline_textq.clone(),
+			&DynamicState::none(),
+			vec![draw.page.qverts.clone(), draw.instances.clone()],
+			draw.qcmds.clone(),
+			(),
+			pc,
+			vec![],
+		)
+		.unwrap();
+}
+
 fn create_render_pass<'a>(gfx: &'a mut Gfx, window: &Window) -> RenderPasses {
 	gfx.get_or_create_render_pass(window.device_ctx().device(), window.swapchain().format()).clone()
 }
 
+fn create_text_image(window: &Window, format: Format) -> Arc<ImageView<Arc<AttachmentImage>>> {
+	let device = window.device_ctx().device();
+	let dimensions = window.images()[0].dimensions();
+	ImageView::new(AttachmentImage::sampled_input_attachment(device.clone(), dimensions, format).unwrap()).unwrap()
+}
+
 fn create_text_framebuffer(
 	render_pass: &Arc<RenderPass>,
 	text_image: &Arc<ImageView<Arc<AttachmentImage>>>,
+	text_color_image: &Arc<ImageView<Arc<AttachmentImage>>>,
+) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+	Arc::new(
+		Framebuffer::start(render_pass.clone())
+			.add(text_image.clone())
+			.unwrap()
+			.add(text_color_image.clone())
+			.unwrap()
+			.build()
+			.unwrap(),
+	)
+}
+
+fn create_label_framebuffer(
+	render_pass: &Arc<RenderPass>,
+	label_image: &Arc<ImageView<Arc<AttachmentImage>>>,
+	label_color_image: &Arc<ImageView<Arc<AttachmentImage>>>,
+	label_depth_image: &Arc<ImageView<Arc<AttachmentImage>>>,
+) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+	Arc::new(
+		Framebuffer::start(render_pass.clone())
+			.add(label_image.clone())
+			.unwrap()
+			.add(label_color_image.clone())
+			.unwrap()
+			.add(label_depth_image.clone())
+			.unwrap()
+			.build()
+			.unwrap(),
+	)
+}
+
+fn create_layer_framebuffer(
+	render_pass: &Arc<RenderPass>,
+	layer_image: &Arc<ImageView<Arc<AttachmentImage>>>,
 ) -> Arc<dyn FramebufferAbstract + Send + Sync> {
-	Arc::new(Framebuffer::start(render_pass.clone()).add(text_image.clone()).unwrap().build().unwrap())
+	Arc::new(Framebuffer::start(render_pass.clone()).add(layer_image.clone()).unwrap().build().unwrap())
 }
 
 fn create_framebuffers(
@@ -350,6 +852,30 @@ const BLEND_ADD: AttachmentBlend = AttachmentBlend {
 	mask_alpha: true,
 };
 
+// windings add up, but alpha holds the fill rule a pixel is resolved with, so it keeps the largest flag written to it
+// instead of counting how many glyphs touched the pixel. that makes nonzero win over even-odd.
+const BLEND_WINDING: AttachmentBlend = AttachmentBlend { alpha_op: BlendOp::Max, ..BLEND_ADD };
+
+// the glyph drawn last decides the color of a pixel. a glyph's triangles can reach past its outline into a
+// neighbour's, so spans with different colors can bleed into each other where glyphs overlap.
+const TEXT_BLEND: [AttachmentBlend; 2] = [BLEND_WINDING, BLEND_REPLACE];
+// labels overlap each other the same way, so the last one drawn also decides a pixel's depth
+const LABEL_BLEND: [AttachmentBlend; 3] = [BLEND_WINDING, BLEND_REPLACE, BLEND_REPLACE];
+
+const BLEND_REPLACE: AttachmentBlend = AttachmentBlend {
+	enabled: false,
+	color_op: BlendOp::Add,
+	color_source: BlendFactor::One,
+	color_destination: BlendFactor::Zero,
+	alpha_op: BlendOp::Add,
+	alpha_source: BlendFactor::One,
+	alpha_destination: BlendFactor::Zero,
+	mask_red: true,
+	mask_green: true,
+	mask_blue: true,
+	mask_alpha: true,
+};
+
 fn create_text_pipeline(
 	window: &Window,
 	render_pass: &Arc<RenderPass>,
@@ -369,7 +895,7 @@ fn create_text_pipeline(
 				depth_range: 0.0..1.0,
 			}))
 			.fragment_shader(device_ctx.fs_text().main_entry_point(), ())
-			.blend_collective(BLEND_ADD)
+			.blend_individual(TEXT_BLEND.iter().cloned())
 			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
 			.build(device_ctx.device().clone())
 			.unwrap(),
@@ -395,13 +921,28 @@ fn create_textq_pipeline(
 				depth_range: 0.0..1.0,
 			}))
 			.fragment_shader(device_ctx.fs_textq().main_entry_point(), ())
-			.blend_collective(BLEND_ADD)
+			.blend_individual(TEXT_BLEND.iter().cloned())
 			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
 			.build(device_ctx.device().clone())
 			.unwrap(),
 	)
 }
 
+// the composite's second output is the coverage of each subpixel, used as that channel's alpha
+const BLEND_SUBPIXEL: AttachmentBlend = AttachmentBlend {
+	enabled: true,
+	color_op: BlendOp::Add,
+	color_source: BlendFactor::Src1Color,
+	color_destination: BlendFactor::OneMinusSrc1Color,
+	alpha_op: BlendOp::Add,
+	alpha_source: BlendFactor::Zero,
+	alpha_destination: BlendFactor::One,
+	mask_red: true,
+	mask_green: true,
+	mask_blue: true,
+	mask_alpha: true,
+};
+
 fn create_text2_pipeline(
 	window: &Window,
 	render_pass: &Arc<RenderPass>,
@@ -421,7 +962,7 @@ fn create_text2_pipeline(
 				depth_range: 0.0..1.0,
 			}))
 			.fragment_shader(device_ctx.fs_text2().main_entry_point(), ())
-			.blend_collective(BLEND_ADD)
+			.blend_collective(BLEND_SUBPIXEL)
 			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
 			.build(device_ctx.device().clone())
 			.unwrap(),
@@ -432,7 +973,335 @@ fn create_text2_pipeline(
 	(pipeline, layout)
 }
 
-#[derive(Debug, Default, Copy, Clone)]
+fn create_label_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input(OneVertexOneInstanceDefinition::<TriangleVertex, ChInstance>::new())
+			.vertex_shader(device_ctx.vs_label().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_label().main_entry_point(), ())
+			.blend_individual(LABEL_BLEND.iter().cloned())
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	)
+}
+
+fn create_labelq_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input(OneVertexOneInstanceDefinition::<TriangleVertex, ChInstance>::new())
+			.vertex_shader(device_ctx.vs_label().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_labelq().main_entry_point(), ())
+			.blend_individual(LABEL_BLEND.iter().cloned())
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	)
+}
+
+// composites labels like text2, but tested against the depth of the scene
+fn create_label2_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> (Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Arc<UnsafeDescriptorSetLayout>) {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	let pipeline = Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input_single_buffer::<TriangleVertex>()
+			.vertex_shader(device_ctx.vs_sprite().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_label2().main_entry_point(), ())
+			.depth_stencil(DepthStencil { depth_write: false, ..DepthStencil::simple_depth_test() })
+			.blend_collective(BLEND_SUBPIXEL)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	);
+
+	let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+
+	(pipeline, layout)
+}
+
+// the layer image holds premultiplied colors
+const BLEND_PREMULTIPLIED: AttachmentBlend = AttachmentBlend {
+	enabled: true,
+	color_op: BlendOp::Add,
+	color_source: BlendFactor::One,
+	color_destination: BlendFactor::OneMinusSrcAlpha,
+	alpha_op: BlendOp::Add,
+	alpha_source: BlendFactor::One,
+	alpha_destination: BlendFactor::OneMinusSrcAlpha,
+	mask_red: true,
+	mask_green: true,
+	mask_blue: true,
+	mask_alpha: true,
+};
+
+// composites one layer of color glyphs from the text framebuffer onto the layers below it
+fn create_layer_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> (Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Arc<UnsafeDescriptorSetLayout>) {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	let pipeline = Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input_single_buffer::<TriangleVertex>()
+			.vertex_shader(device_ctx.vs_sprite().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_layer().main_entry_point(), ())
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	);
+
+	let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+
+	(pipeline, layout)
+}
+
+// draws the stacked color glyph layers to the screen
+fn create_image_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> (Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Arc<UnsafeDescriptorSetLayout>) {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	let pipeline = Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input_single_buffer::<TriangleVertex>()
+			.vertex_shader(device_ctx.vs_sprite().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_image().main_entry_point(), ())
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	);
+
+	let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+
+	(pipeline, layout)
+}
+
+// draws a text's stroke, shadow and glow from its windings into the layer image
+fn create_effects_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> (Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Arc<UnsafeDescriptorSetLayout>) {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	let pipeline = Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input_single_buffer::<TriangleVertex>()
+			.vertex_shader(device_ctx.vs_sprite().main_entry_point(), ())
+			.triangle_list()
+			.viewports_fixed_scissors_dynamic(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_effects().main_entry_point(), ())
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	);
+
+	let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+
+	(pipeline, layout)
+}
+
+fn create_bitmap_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> (Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Arc<UnsafeDescriptorSetLayout>) {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	let pipeline = Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input(OneVertexOneInstanceDefinition::<TriangleVertex, BitmapInstance>::new())
+			.vertex_shader(device_ctx.vs_bitmap().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_bitmap().main_entry_point(), ())
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	);
+
+	let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+
+	(pipeline, layout)
+}
+
+fn create_sdf_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input(OneVertexOneInstanceDefinition::<TriangleVertex, BitmapInstance>::new())
+			.vertex_shader(device_ctx.vs_bitmap().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_sdf().main_entry_point(), ())
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	)
+}
+
+// glyph images in labels don't need a winding pass, so they're drawn straight to the screen
+fn create_label_bitmap_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input(OneVertexOneInstanceDefinition::<TriangleVertex, BitmapInstance>::new())
+			.vertex_shader(device_ctx.vs_label_bitmap().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_bitmap().main_entry_point(), ())
+			.depth_stencil(DepthStencil { depth_write: false, ..DepthStencil::simple_depth_test() })
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	)
+}
+
+fn create_label_sdf_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input(OneVertexOneInstanceDefinition::<TriangleVertex, BitmapInstance>::new())
+			.vertex_shader(device_ctx.vs_label_bitmap().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_sdf().main_entry_point(), ())
+			.depth_stencil(DepthStencil { depth_write: false, ..DepthStencil::simple_depth_test() })
+			.blend_collective(BLEND_PREMULTIPLIED)
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	)
+}
+
+fn create_rect_pipeline(
+	window: &Window,
+	render_pass: &Arc<RenderPass>,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+	let device_ctx = window.device_ctx();
+	let dimensions = window.images()[0].dimensions();
+
+	Arc::new(
+		GraphicsPipeline::start()
+			.vertex_input_single_buffer::<TriangleVertex>()
+			.vertex_shader(device_ctx.vs_rect().main_entry_point(), ())
+			.triangle_list()
+			.viewports_dynamic_scissors_irrelevant(1)
+			.viewports(iter::once(Viewport {
+				origin: [0.0, 0.0],
+				dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+				depth_range: 0.0..1.0,
+			}))
+			.fragment_shader(device_ctx.fs_rect().main_entry_point(), ())
+			.blend_alpha_blending()
+			.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+			.build(device_ctx.device().clone())
+			.unwrap(),
+	)
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct TriangleVertex {
 	pub v_pos: [f32; 2],
 }
@@ -0,0 +1,14 @@
+#version 450
+
+layout (set = 0, binding = 0) uniform sampler2D atlas;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) flat in vec4 f_color;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+	// colors are premultiplied where they're drawn, but the glyph images aren't
+	vec4 texel = texture(atlas, uv);
+	color = vec4(texel.rgb * texel.a, texel.a) * vec4(f_color.rgb * f_color.a, f_color.a);
+}
@@ -0,0 +1,54 @@
+#version 450
+
+layout(location = 0) in vec2 v_pos;
+layout(location = 1) in vec2 bm_pos;
+layout(location = 2) in vec2 bm_size;
+layout(location = 3) in vec2 bm_uv;
+layout(location = 4) in vec2 bm_uv_size;
+layout(location = 5) in vec4 bm_color;
+
+layout(location = 0) out vec2 uv;
+layout(location = 1) flat out vec4 f_color;
+
+#ifdef LABELS
+layout(push_constant) uniform PushConstant {
+	vec4 label_rot;
+	vec4 label_pos;
+	vec4 camera_rot;
+	vec4 camera_pos;
+	vec4 camera_proj;
+	vec2 target_size;
+	vec2 origin;
+} pc;
+
+vec4 quat_inv(vec4 quat) {
+	return vec4(-quat.xyz, quat.w) / dot(quat, quat);
+}
+vec3 quat_mul(vec4 quat, vec3 vec) {
+	return cross(quat.xyz, cross(quat.xyz, vec) + vec * quat.w) * 2.0 + vec;
+}
+vec4 perspective(vec4 proj, vec3 pos) {
+	return vec4(pos.xy * proj.xy, -pos.z * proj.z + proj.w, pos.z);
+}
+#else
+layout(push_constant) uniform PushConstant {
+	vec2 pos;
+	vec2 target_size;
+} pc;
+#endif
+
+void main() {
+	uv = bm_uv + v_pos * bm_uv_size;
+	f_color = bm_color;
+
+#ifdef LABELS
+	// layouts have y pointing down
+	vec2 position_ls = bm_pos + v_pos * bm_size + pc.origin;
+	vec3 position_ws = quat_mul(pc.label_rot, vec3(position_ls.x, -position_ls.y, 0)) + pc.label_pos.xyz;
+	vec3 position_cs = quat_mul(quat_inv(pc.camera_rot), position_ws - pc.camera_pos.xyz);
+	gl_Position = perspective(pc.camera_proj, position_cs);
+	gl_Position.y = -gl_Position.y;
+#else
+	gl_Position = vec4((bm_pos + v_pos * bm_size) * 2 / pc.target_size + pc.pos, 0, 1);
+#endif
+}
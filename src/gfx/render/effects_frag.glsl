@@ -0,0 +1,91 @@
+#version 450
+
+layout (set = 0, binding = 0) uniform sampler2D text;
+
+layout(location = 0) in vec2 f_pos;
+
+// premultiplied, since it's stacked in the layer image
+layout(location = 0) out vec4 color;
+
+layout(push_constant) uniform PushConstant {
+	vec4 stroke_color;
+	vec4 shadow_color;
+	vec4 glow_color;
+	vec2 shadow_offset;
+	vec2 target_size;
+	float stroke_width;
+	float shadow_blur;
+	float glow_radius;
+} pc;
+
+// past this many pixels in each direction, samples are spread further apart instead of taking longer
+const float MAX_STEPS = 8;
+
+// how much of a pixel the text covers, without subpixels. the windings are resolved the same way text2 does it.
+float coverage(vec2 uv) {
+	vec4 windings = texture(text, uv);
+	vec3 second = round(windings.rgb / 64);
+	vec3 first = windings.rgb - second * 64;
+	vec3 samples = windings.a < 0.5
+		? (mod(abs(first), 2) + mod(abs(second), 2)) / 2
+		: (vec3(notEqual(first, vec3(0))) + vec3(notEqual(second, vec3(0)))) / 2;
+	return (samples.r + samples.g + samples.b) / 3;
+}
+
+// the most coverage within a radius, which grows the text outwards
+float dilate(vec2 uv, float radius) {
+	float spacing = max(1, radius / MAX_STEPS);
+	float steps = ceil(radius / spacing);
+	float result = coverage(uv);
+	for (float y = -steps; y <= steps; y++) {
+		for (float x = -steps; x <= steps; x++) {
+			vec2 offset = vec2(x, y) * spacing;
+			// the edge of the disk is antialiased
+			float weight = clamp(radius + 0.5 - length(offset), 0, 1);
+			result = max(result, coverage(uv + offset / pc.target_size) * weight);
+		}
+	}
+	return result;
+}
+
+// the coverage averaged over a disk, weighted towards its center
+float blur(vec2 uv, float radius) {
+	if (radius < 0.5) return coverage(uv);
+
+	float spacing = max(1, radius / MAX_STEPS);
+	float steps = ceil(radius / spacing);
+	float total = 0;
+	float weights = 0;
+	for (float y = -steps; y <= steps; y++) {
+		for (float x = -steps; x <= steps; x++) {
+			vec2 offset = vec2(x, y) * spacing;
+			float weight = max(0, 1 - length(offset) / (radius + 1));
+			total += coverage(uv + offset / pc.target_size) * weight;
+			weights += weight;
+		}
+	}
+	return total / weights;
+}
+
+vec4 over(vec4 top, vec4 bottom) {
+	return top + bottom * (1 - top.a);
+}
+
+void main() {
+	vec2 uv = f_pos / 2 + 0.5;
+
+	color = vec4(0);
+	if (pc.shadow_color.a > 0) {
+		float alpha = blur(uv - pc.shadow_offset / pc.target_size, pc.shadow_blur) * pc.shadow_color.a;
+		color = vec4(pc.shadow_color.rgb * alpha, alpha);
+	}
+	if (pc.glow_color.a > 0) {
+		// a blur fades to half at the text's edges, so it's doubled to start the glow at full strength
+		float alpha = min(blur(uv, pc.glow_radius) * 2, 1) * pc.glow_color.a;
+		color = over(vec4(pc.glow_color.rgb * alpha, alpha), color);
+	}
+	if (pc.stroke_color.a > 0 && pc.stroke_width > 0) {
+		float alpha = dilate(uv, pc.stroke_width) * pc.stroke_color.a;
+		color = over(vec4(pc.stroke_color.rgb * alpha, alpha), color);
+	}
+}
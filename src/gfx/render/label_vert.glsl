@@ -0,0 +1,57 @@
+#version 460
+
+layout(location = 0) in vec2 v_pos;
+layout(location = 1) in vec2 ch_pos;
+layout(location = 2) in vec2 ch_scale;
+layout(location = 3) in vec4 ch_color;
+layout(location = 4) in float ch_fill;
+
+layout(location = 0) out vec2 uv;
+layout(location = 1) out vec3 f_color;
+layout(location = 2) flat out vec4 f_ch_color;
+layout(location = 3) flat out float f_fill;
+
+layout(push_constant) uniform PushConstant {
+	vec4 label_rot;
+	vec4 label_pos;
+	vec4 camera_rot;
+	vec4 camera_pos;
+	vec4 camera_proj;
+	vec2 target_size;
+	vec2 origin;
+} pc;
+
+vec4 quat_inv(vec4 quat) {
+	return vec4(-quat.xyz, quat.w) / dot(quat, quat);
+}
+vec3 quat_mul(vec4 quat, vec3 vec) {
+	return cross(quat.xyz, cross(quat.xyz, vec) + vec * quat.w) * 2.0 + vec;
+}
+vec4 perspective(vec4 proj, vec3 pos) {
+	return vec4(pos.xy * proj.xy, -pos.z * proj.z + proj.w, pos.z);
+}
+
+void main() {
+	float u = mod(gl_VertexIndex + 2, 3.0) / 2;
+	uv = vec2(u, floor(u));
+
+	float samplex = mod(gl_DrawID, 3);
+	float sampley = floor(mod(gl_DrawID, 6) / 3);
+
+	// each channel is a column of samples, with the second row counted in multiples of 64
+	f_color = vec3(0);
+	f_color[int(samplex)] = sampley * 63 + 1;
+	f_ch_color = ch_color;
+	f_fill = ch_fill;
+
+	// layouts have y pointing down
+	vec2 position_ls = v_pos * ch_scale + ch_pos + pc.origin;
+	vec3 position_ws = quat_mul(pc.label_rot, vec3(position_ls.x, -position_ls.y, 0)) + pc.label_pos.xyz;
+	vec3 position_cs = quat_mul(quat_inv(pc.camera_rot), position_ws - pc.camera_pos.xyz);
+	gl_Position = perspective(pc.camera_proj, position_cs);
+	gl_Position.y = -gl_Position.y;
+
+	// the samples are spread in screen space, so labels are as smooth up close as they are far away
+	vec2 offset = vec2((samplex - 1) / 2, (sampley - 0.5) * 2 / 3);
+	gl_Position.xy += offset / pc.target_size * gl_Position.w;
+}
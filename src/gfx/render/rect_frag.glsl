@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) flat in vec4 f_color;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+	color = f_color;
+}
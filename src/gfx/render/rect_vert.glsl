@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) in vec2 v_pos;
+
+layout(location = 0) flat out vec4 f_color;
+
+layout(push_constant) uniform PushConstant {
+	vec4 color;
+	vec2 pos;
+	vec2 size;
+} pc;
+
+void main() {
+	f_color = pc.color;
+	gl_Position = vec4(pc.pos + v_pos * pc.size, 0, 1);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout (set = 0, binding = 0) uniform sampler2D atlas;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) flat in vec4 f_color;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+	// the outline is at 0.5, and fwidth is how much the distance changes over one screen pixel at any scale or angle
+	float distance = texture(atlas, uv).r;
+	float alpha = clamp((distance - 0.5) / fwidth(distance) + 0.5, 0, 1) * f_color.a;
+	color = vec4(f_color.rgb * alpha, alpha);
+}
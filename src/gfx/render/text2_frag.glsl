@@ -1,35 +1,55 @@
 #version 450
 
 layout (set = 0, binding = 0) uniform sampler2D text;
+layout (set = 0, binding = 1) uniform sampler2D text_color;
+#ifdef LABELS
+layout (set = 0, binding = 2) uniform sampler2D label_depth;
+#endif
 
 layout(location = 0) in vec2 f_pos;
 
+#ifdef COLOR_LAYERS
+// color glyph layers are stacked in an offscreen image first, so they're premultiplied and without subpixels
 layout(location = 0) out vec4 color;
+#else
+// dual-source blending lets each subpixel have its own alpha
+layout(location = 0, index = 0) out vec4 color;
+layout(location = 0, index = 1) out vec4 alpha;
+#endif
+
+// how much of each column of samples is covered, from their windings. alpha is 1 wherever a nonzero font or a
+// decoration was drawn, so only pixels that just even-odd fonts were drawn to use that rule.
+vec3 fill(vec4 windings) {
+	vec3 second = round(windings.rgb / 64);
+	vec3 first = windings.rgb - second * 64;
+	if (windings.a < 0.5) return (mod(abs(first), 2) + mod(abs(second), 2)) / 2;
+	return (vec3(notEqual(first, vec3(0))) + vec3(notEqual(second, vec3(0)))) / 2;
+}
 
 void main() {
 	vec2 uv = f_pos / 2 + 0.5;
 
 	vec2 offset = vec2(abs(dFdx(uv.x)), 0);
-	float rgbl = texture(text, uv + offset).b * 255;
-	vec3 rgbc = texture(text, uv).rgb * 255;
-	float rgbr = texture(text, uv - offset).r * 255;
-
-	float rgblh = mod(floor(rgbl / 16), 2);
-	float rgbll = mod(mod(rgbl, 16), 2);
-	vec3 rgbch = mod(floor(rgbc / 16), 2);
-	vec3 rgbcl = mod(mod(rgbc, 16), 2);
-	float rgbrh = mod(floor(rgbr / 16), 2);
-	float rgbrl = mod(mod(rgbr, 16), 2);
-
-	float alphaL = (rgblh + rgbll) / 2;
-	vec3 alphaC = (rgbch + rgbcl) / 2;
-	float alphaR = (rgbrh + rgbrl) / 2;
+	float alphaL = fill(texture(text, uv + offset)).b;
+	vec3 alphaC = fill(texture(text, uv));
+	float alphaR = fill(texture(text, uv - offset)).r;
 
 	vec3 colors = vec3(
 		(alphaC.y + alphaC.z + alphaR) / 3,
 		(alphaC.x + alphaC.y + alphaC.z) / 3,
 		(alphaL + alphaC.x + alphaC.y) / 3
 	);
-	// vec3 colors = (rgbch + rgbcl) / 2;
-	color = vec4(colors, 0);
+	// vec3 colors = alphaC;
+#ifdef LABELS
+	if (colors == vec3(0)) discard;
+	gl_FragDepth = texture(label_depth, uv).r;
+#endif
+	vec4 ch_color = texture(text_color, uv);
+#ifdef COLOR_LAYERS
+	float coverage = (colors.r + colors.g + colors.b) / 3 * ch_color.a;
+	color = vec4(ch_color.rgb * coverage, coverage);
+#else
+	color = vec4(ch_color.rgb, 1);
+	alpha = vec4(colors * ch_color.a, 1);
+#endif
 }
@@ -2,9 +2,21 @@
 
 layout(location = 0) in vec2 uv;
 layout(location = 1) in vec3 f_color;
+layout(location = 2) flat in vec4 f_ch_color;
+layout(location = 3) flat in float f_fill;
 
 layout(location = 0) out vec4 color;
+layout(location = 1) out vec4 ch_color;
+#ifdef LABELS
+layout(location = 2) out vec4 depth;
+#endif
 
 void main() {
-	color = vec4(f_color, 1);
+	// windings count up for triangles facing one way and down for the other. alpha marks the fill rule the pixel is
+	// resolved with: 0 for even-odd fonts, and 1 for nonzero fonts and decorations.
+	color = vec4(gl_FrontFacing ? f_color : -f_color, f_fill);
+	ch_color = f_ch_color;
+#ifdef LABELS
+	depth = vec4(gl_FragCoord.z);
+#endif
 }
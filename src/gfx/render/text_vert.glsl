@@ -2,14 +2,18 @@
 
 layout(location = 0) in vec2 v_pos;
 layout(location = 1) in vec2 ch_pos;
+layout(location = 2) in vec2 ch_scale;
+layout(location = 3) in vec4 ch_color;
+layout(location = 4) in float ch_fill;
 
 layout(location = 0) out vec2 uv;
 layout(location = 1) out vec3 f_color;
+layout(location = 2) flat out vec4 f_ch_color;
+layout(location = 3) flat out float f_fill;
 
 layout(push_constant) uniform PushConstant {
 	vec2 pos;
 	vec2 target_size;
-	float scale;
 } pc;
 
 void main() {
@@ -19,9 +23,12 @@ void main() {
 	float samplex = mod(gl_DrawID, 3);
 	float sampley = floor(mod(gl_DrawID, 6) / 3);
 
+	// each channel is a column of samples, with the second row counted in multiples of 64
 	f_color = vec3(0);
-	f_color[int(samplex)] = 1.0 / 255 * (sampley * 15 + 1);
+	f_color[int(samplex)] = sampley * 63 + 1;
+	f_ch_color = ch_color;
+	f_fill = ch_fill;
 
 	vec2 offset = vec2((samplex - 1) / 2, (sampley - 0.5) * 2 / 3);
-	gl_Position = vec4(((v_pos + ch_pos) * pc.scale + offset) / pc.target_size + pc.pos, 0, 1);
+	gl_Position = vec4(((v_pos * ch_scale + ch_pos) * 2 + offset) / pc.target_size + pc.pos, 0, 1);
 }
@@ -1,23 +1,77 @@
-use crate::gfx::render::TriangleVertex;
-use async_std::{channel, channel::Sender, task::block_on};
-use font_kit::{font::Font as KFont, hinting::HintingOptions, outline::OutlineSink};
-use harfbuzz_rs::{shape, Face, Font as HFont, GlyphInfo, GlyphPosition, UnicodeBuffer};
+mod atlas;
+mod cache;
+pub mod field;
+pub mod layout;
+pub mod rich;
+mod sdf;
+
+use crate::gfx::{
+	render::TriangleVertex,
+	text::{
+		atlas::{Atlas, AtlasImage, BitmapGlyph},
+		cache::{CacheKey, Cached, GlyphGeometry},
+		layout::{Layout, LayoutOptions, TextMetrics},
+		rich::{RichText, TextStyle},
+	},
+};
+use async_std::{
+	channel,
+	channel::{Receiver, Sender, TryRecvError},
+	task::block_on,
+};
+use font_kit::{
+	error::FontLoadingError, font::Font as KFont, hinting::HintingOptions, metrics::Metrics, outline::OutlineSink,
+};
+use harfbuzz_rs::{Blob, Face, Font as HFont, HarfbuzzObject, Owned};
+use harfbuzz_sys as hb;
 use lazy_static::lazy_static;
-use nalgebra::Vector2;
-use pathfinder_geometry::{line_segment::LineSegment2F, vector::Vector2F};
-use std::{collections::HashMap, sync::Arc, thread};
+use nalgebra::{Quaternion, Vector2, Vector3};
+use pathfinder_geometry::{line_segment::LineSegment2F, rect::RectF, vector::Vector2F};
+use std::{
+	collections::{HashMap, HashSet},
+	env, fs,
+	path::{Path, PathBuf},
+	ptr,
+	sync::Arc,
+	thread,
+};
+use ttf_parser as ttf;
 use unic_char_range::CharRange;
-use unic_ucd_block::BlockIter;
+use unic_ucd_block::{Block, BlockIter};
 use vulkano::{
-	buffer::{cpu_pool::CpuBufferPoolChunk, BufferUsage, CpuBufferPool, ImmutableBuffer},
+	buffer::{cpu_pool::CpuBufferPoolChunk, CpuBufferPool, ImmutableBuffer},
 	command_buffer::{DrawIndexedIndirectCommand, DrawIndirectCommand},
-	device::{Device, Queue},
+	device::Queue,
+	format::Format,
 	memory::pool::StdMemoryPool,
+	sync,
 	sync::GpuFuture,
 };
 
-type LoadFontReturn = (BlockInfo, HashMap<u32, GlyphInfo2>, Box<dyn GpuFuture + Send + Sync>);
-type LoadFontParams = (&'static str, Arc<Queue>, Sender<LoadFontReturn>);
+struct LoadFontReturn {
+	// glyphs drawn with distance fields don't need one
+	page: Option<GlyphPage>,
+	glyphs: Vec<(u32, GlyphInfo2)>,
+	atlas: Option<Atlas>,
+	sdf: Option<Atlas>,
+	future: Box<dyn GpuFuture + Send + Sync>,
+}
+
+struct LoadFontParams {
+	data: Arc<[u8]>,
+	index: u32,
+	hash: u64,
+	cache_dir: Option<PathBuf>,
+	variations: Vec<Variation>,
+	// font-kit's copy of the data, which hinted outlines are read from
+	hinting: Option<(Arc<Vec<u8>>, Hinting)>,
+	renderer: Renderer,
+	glyphs: Vec<u32>,
+	// the glyphs that weren't already requested for the other renderer, whose embedded images are loaded with them
+	images: Vec<u32>,
+	queue: Arc<Queue>,
+	send: Sender<LoadFontReturn>,
+}
 
 lazy_static! {
 	static ref BLOCKS: HashMap<&'static str, CharRange> =
@@ -27,49 +81,64 @@ lazy_static! {
 
 		thread::spawn(move || {
 			block_on(async {
-				let font = KFont::from_path("res/Roboto-Regular.ttf", 0).unwrap();
-
-				while let Ok((block, queue, send)) = recv.recv().await {
-					let mut sink = TriangleBuilder::new();
-					let mut glyph_info = HashMap::new();
-
-					for ch in BLOCKS[block] {
-						if let Some(glyph_id) = font.glyph_for_char(ch) {
-							let index_start = sink.indices.len();
-							let vert_start = sink.verts.len() - 2;
-							let qvert_start = sink.qverts.len();
-							font.outline(glyph_id, HintingOptions::None, &mut sink).unwrap();
-
-							glyph_info.insert(glyph_id, GlyphInfo2 {
-								index_count: (sink.indices.len() - index_start) as _,
-								first_index: index_start as _,
-								vertex_offset: vert_start as _,
-								qvertex_count: (sink.qverts.len() - qvert_start) as _,
-								qvertex_offset: qvert_start as _,
+				while let Ok(params) = recv.recv().await {
+					let LoadFontParams {
+						data,
+						index,
+						hash,
+						cache_dir,
+						variations,
+						hinting,
+						renderer,
+						glyphs,
+						images,
+						queue,
+						send,
+					} = params;
+					let key = CacheKey::new(hash, &glyphs);
+					let path = cache_dir.map(|dir| key.path(&dir));
+					let atlas = atlas::load_atlas(&data, index, &images, &queue);
+					let loaded = match renderer {
+						Renderer::Winding => {
+							let geometry = cached(path.as_deref(), key, || {
+								tessellate(&data, index, &variations, hinting.as_ref(), &glyphs)
 							});
+							geometry.map(|mut geometry| {
+								let glyphs: Vec<_> = geometry.glyphs.drain(..).collect();
+								let (page, future) = geometry.upload(&queue);
+								(Some(page), glyphs, None, future)
+							})
+						},
+						// distance fields are made from the outlines directly, so nothing is tessellated for them
+						Renderer::Sdf => {
+							let fields =
+								cached(path.as_deref(), key, || sdf::distance_fields(&data, index, &variations, &glyphs));
+							fields.map(|fields| {
+								// glyphs without an outline, like spaces, have no field. they get empty windings
+								// instead, so they aren't drawn as placeholders.
+								let outlined: HashSet<_> = fields.fields.iter().map(|&(glyph, ..)| glyph).collect();
+								let empty: Vec<_> = glyphs
+									.iter()
+									.filter(|glyph| !outlined.contains(glyph))
+									.map(|&glyph| (glyph, GlyphInfo2::default()))
+									.collect();
+								let sdf = atlas::pack(fields.fields, Format::R8Unorm, &queue);
+								let future: Box<dyn GpuFuture + Send + Sync> = Box::new(sync::now(queue.device().clone()));
+								(None, empty, sdf, future)
+							})
+						},
+					};
 
-							sink.reset();
-						}
-					}
-
-					println!("tris {} qtris {}", sink.indices.len() / 3, sink.qverts.len() / 3);
-
-					let (verts, verts_future) =
-						ImmutableBuffer::from_iter(sink.verts.into_iter(), BufferUsage::vertex_buffer(), queue.clone())
-							.unwrap();
-					let (indices, indices_future) = ImmutableBuffer::from_iter(
-						sink.indices.into_iter(),
-						BufferUsage::index_buffer(),
-						queue.clone(),
-					)
-					.unwrap();
-					let (qverts, qverts_future) =
-						ImmutableBuffer::from_iter(sink.qverts.into_iter(), BufferUsage::vertex_buffer(), queue)
-							.unwrap();
-
-					let block_info = BlockInfo { indices, verts, qverts };
-					let future = Box::new(verts_future.join(indices_future).join(qverts_future));
-					send.send((block_info, glyph_info, future)).await.unwrap();
+					let (page, glyphs, sdf, future) = match loaded {
+						Ok(loaded) => loaded,
+						// dropping `send` tells the font to stop waiting, and its glyphs stay placeholders
+						Err(err) => {
+							eprintln!("failed to load glyphs: {}", err);
+							continue;
+						},
+					};
+					// the font stops waiting for its pages when its variations change
+					send.send(LoadFontReturn { page, glyphs, atlas, sdf, future }).await.ok();
 				}
 			})
 		});
@@ -78,100 +147,1119 @@ lazy_static! {
 	};
 }
 
-async fn load_font(block: &'static str, queue: &Arc<Queue>) -> LoadFontReturn {
+fn load_font(font: &Font, glyphs: Vec<u32>, images: Vec<u32>, renderer: Renderer) -> Receiver<LoadFontReturn> {
 	let (send, recv) = channel::bounded(1);
-	LOAD_FONT.send((block, queue.clone(), send)).await.unwrap();
-	recv.recv().await.unwrap()
+	let hinting = match renderer {
+		Renderer::Winding => font.hinting_data.clone().map(|data| (data, font.hinting)),
+		Renderer::Sdf => None,
+	};
+	let hinted = hinting.as_ref().map_or(Hinting::None, |&(_, hinting)| hinting);
+	let params = LoadFontParams {
+		data: font.data.clone(),
+		index: font.index,
+		hash: font_hash(font.data_hash, font.index, &font.variations, hinted, renderer),
+		cache_dir: font.cache_dir.clone(),
+		variations: font.variations.clone(),
+		hinting,
+		renderer,
+		glyphs,
+		images,
+		queue: font.queue.clone(),
+		send,
+	};
+	block_on(LOAD_FONT.send(params)).unwrap();
+	recv
+}
+
+// reads what was made from a batch of glyphs from the cache, or makes it and saves it there
+fn cached<T: Cached>(
+	path: Option<&Path>,
+	key: CacheKey,
+	make: impl FnOnce() -> Result<T, FontLoadingError>,
+) -> Result<T, FontLoadingError> {
+	if let Some(cached) = path.and_then(|path| T::read(path, key)) {
+		return Ok(cached);
+	}
+
+	let made = make()?;
+	// the cache only saves time, so the glyphs are still usable if it can't be written
+	if let Some(path) = path {
+		if let Err(err) = made.write(path, key) {
+			eprintln!("failed to write glyph cache {}: {}", path.display(), err);
+		}
+	}
+	Ok(made)
+}
+
+// outlines come from ttf-parser, which can also vary them. only hinted ones come from font-kit, which can only outline
+// the default instance, so fonts with variations set are never hinted.
+struct Outliner<'a> {
+	face: ttf::Face<'a>,
+	hinted: Option<(KFont, HintingOptions)>,
+}
+impl<'a> Outliner<'a> {
+	fn new(
+		data: &'a [u8],
+		index: u32,
+		variations: &[Variation],
+		hinting: Option<&(Arc<Vec<u8>>, Hinting)>,
+	) -> Result<Self, FontLoadingError> {
+		let mut face = ttf::Face::from_slice(data, index).map_err(|_| FontLoadingError::Parse)?;
+		for variation in variations {
+			face.set_variation(ttf::Tag::from_bytes(&variation.tag), variation.value);
+		}
+		let hinted = match hinting {
+			Some((data, hinting)) if variations.is_empty() && *hinting != Hinting::None => {
+				Some((KFont::from_bytes(data.clone(), index)?, hinting.options()))
+			},
+			_ => None,
+		};
+		Ok(Self { face, hinted })
+	}
+
+	fn units_per_em(&self) -> u32 {
+		units_per_em(&self.face)
+	}
+
+	// returns false if the glyph couldn't be outlined
+	fn outline(&self, glyph: u32, sink: &mut impl OutlineSink) -> bool {
+		match &self.hinted {
+			// hinted outlines are still in font units, so they're tessellated the same way
+			Some((font, hinting)) => font.outline(glyph, *hinting, sink).is_ok(),
+			// glyphs without an outline, like spaces, return None too, so this can't be treated as an error
+			None => {
+				self.face.outline_glyph(ttf::GlyphId(glyph as _), &mut VariedSink(sink));
+				true
+			},
+		}
+	}
+}
+
+fn tessellate(
+	data: &[u8],
+	index: u32,
+	variations: &[Variation],
+	hinting: Option<&(Arc<Vec<u8>>, Hinting)>,
+	glyphs: &[u32],
+) -> Result<GlyphGeometry, FontLoadingError> {
+	let outliner = Outliner::new(data, index, variations, hinting)?;
+	let mut sink = TriangleBuilder::new();
+	let mut glyph_info = vec![];
+
+	for &glyph_id in glyphs {
+		let index_start = sink.indices.len();
+		let vert_start = sink.verts.len() - 2;
+		let qvert_start = sink.qverts.len();
+		if outliner.outline(glyph_id, &mut sink) {
+			glyph_info.push((glyph_id, GlyphInfo2 {
+				page: 0,
+				index_count: (sink.indices.len() - index_start) as _,
+				first_index: index_start as _,
+				vertex_offset: vert_start as _,
+				qvertex_count: (sink.qverts.len() - qvert_start) as _,
+				qvertex_offset: qvert_start as _,
+			}));
+		}
+
+		// a glyph that failed partway through still moved the pen, so the next one needs a fresh start either way
+		sink.reset();
+	}
+
+	Ok(sink.finish(glyph_info))
 }
 
 pub struct Font {
-	pub scale: f32,
-	pub block_info: HashMap<&'static str, BlockInfo>,
+	// shared with harfbuzz and the loading thread, so it's never copied
+	data: Arc<[u8]>,
+	// font-kit needs its own copy to read hinted outlines from, so it's only made for hinted fonts
+	hinting_data: Option<Arc<Vec<u8>>>,
+	// large fonts take a while to hash, so the data is only hashed once
+	data_hash: u64,
+	index: u32,
+	variations: Vec<Variation>,
+	hinting: Hinting,
+	// for text that doesn't choose its own
+	renderer: Renderer,
+	fill_rule: FillRule,
+	cache_dir: Option<PathBuf>,
+	queue: Arc<Queue>,
+	hfont: Owned<HFont<'static>>,
+	metrics: Metrics,
+	decoration_metrics: DecorationMetrics,
+	pages: Vec<GlyphPage>,
+	loading: Vec<Receiver<LoadFontReturn>>,
 	glyph_info: HashMap<u32, GlyphInfo2>,
-	cmd_pool: CpuBufferPool<DrawIndexedIndirectCommand>,
-	cmd_pool2: CpuBufferPool<DrawIndirectCommand>,
-	instance_pool: CpuBufferPool<ChInstance>,
+	// glyphs with embedded images, and glyphs drawn with distance fields, are drawn from these instead of their
+	// outlines
+	atlases: Vec<AtlasImage>,
+	bitmaps: HashMap<u32, BitmapGlyph>,
+	sdf_glyphs: HashMap<u32, BitmapGlyph>,
+	// glyphs are loaded separately for each renderer they're drawn with
+	requested_blocks: HashSet<(&'static str, Renderer)>,
+	requested_glyphs: HashSet<(u32, Renderer)>,
+	placeholder_glyph: GlyphInfo2,
+	square_glyph: GlyphInfo2,
+	upload_future: Option<Box<dyn GpuFuture + Send + Sync>>,
 }
 impl Font {
-	pub fn new(device: &Arc<Device>, px_per_em: f32) -> Self {
-		let kfont = KFont::from_path("res/Roboto-Regular.ttf", 0).unwrap();
-		let scale = px_per_em * 2.0 / kfont.metrics().units_per_em as f32;
+	pub fn from_path(queue: &Arc<Queue>, path: impl AsRef<Path>, index: u32) -> Result<Self, FontLoadingError> {
+		let data = fs::read(path).map_err(FontLoadingError::Io)?;
+		Self::from_bytes(queue, data, index)
+	}
 
-		Self {
-			scale,
-			block_info: HashMap::new(),
+	// shared data, like an Arc<[u8]> that other fonts or systems also hold, is used without being copied
+	pub fn from_bytes(queue: &Arc<Queue>, data: impl Into<Arc<[u8]>>, index: u32) -> Result<Self, FontLoadingError> {
+		let data = data.into();
+		let face = ttf::Face::from_slice(&data, index).map_err(|_| FontLoadingError::Parse)?;
+		let metrics = face_metrics(&face);
+		let cff =
+			face.has_table(ttf::TableName::CompactFontFormat) || face.has_table(ttf::TableName::CompactFontFormat2);
+		let hfont = HFont::new(Face::new(Blob::with_bytes_owned(data.clone(), |data| &**data), index));
+
+		// page 0 holds the box that's drawn in place of glyphs that aren't loaded
+		let em = metrics.units_per_em as f32;
+		let height = if metrics.cap_height > 0.0 { metrics.cap_height } else { em * 0.7 };
+		let mut sink = TriangleBuilder::new();
+		sink.rect(Vector2F::new(em * 0.05, 0.0), Vector2F::new(em * 0.55, height));
+		// the inner rect winds the other way, so it's a hole with either fill rule
+		sink.rect(Vector2F::new(em * 0.5, em * 0.05), Vector2F::new(em * 0.1, height - em * 0.05));
+		let placeholder_glyph = GlyphInfo2 {
+			page: 0,
+			index_count: sink.indices.len() as _,
+			first_index: 0,
+			vertex_offset: 0,
+			qvertex_count: 0,
+			qvertex_offset: 0,
+		};
+		// along with a unit square sitting on the origin, which decorations are stretched from. it winds the same way
+		// as the font's outer contours, clockwise for TrueType and counterclockwise for CFF, so it adds to a glyph's
+		// windings where they overlap instead of cancelling them out.
+		sink.reset();
+		let square_start = (sink.indices.len(), sink.verts.len() - 2);
+		if cff {
+			sink.rect(Vector2F::zero(), Vector2F::splat(1.0));
+		} else {
+			sink.rect(Vector2F::new(1.0, 0.0), Vector2F::new(0.0, 1.0));
+		}
+		let square_glyph = GlyphInfo2 {
+			page: 0,
+			index_count: (sink.indices.len() - square_start.0) as _,
+			first_index: square_start.0 as _,
+			vertex_offset: square_start.1 as _,
+			qvertex_count: 0,
+			qvertex_offset: 0,
+		};
+		let (placeholder, upload_future) = sink.finish(vec![]).upload(queue);
+
+		let data_hash = cache::hash(&data);
+
+		Ok(Self {
+			data,
+			hinting_data: None,
+			data_hash,
+			index,
+			variations: vec![],
+			hinting: Hinting::None,
+			renderer: Renderer::Winding,
+			fill_rule: FillRule::NonZero,
+			cache_dir: Some(env::temp_dir().join("glyph-cache")),
+			queue: queue.clone(),
+			hfont,
+			decoration_metrics: DecorationMetrics::new(&data, index, &[], &metrics),
+			metrics,
+			pages: vec![placeholder],
+			loading: vec![],
 			glyph_info: HashMap::new(),
-			cmd_pool: CpuBufferPool::indirect_buffer(device.clone()),
-			cmd_pool2: CpuBufferPool::indirect_buffer(device.clone()),
-			instance_pool: CpuBufferPool::vertex_buffer(device.clone()),
+			atlases: vec![],
+			bitmaps: HashMap::new(),
+			sdf_glyphs: HashMap::new(),
+			requested_blocks: HashSet::new(),
+			requested_glyphs: HashSet::new(),
+			placeholder_glyph,
+			square_glyph,
+			upload_future: Some(upload_future),
+		})
+	}
+
+	// places the font at a point on its variable axes, for both shaping and outlines
+	pub fn variations(mut self, variations: &[Variation]) -> Self {
+		self.set_variations(variations);
+		self
+	}
+
+	// any glyphs that were already loaded have to be loaded again, so it's best not to call this every frame
+	pub fn set_variations(&mut self, variations: &[Variation]) {
+		let hb_variations: Vec<_> = variations
+			.iter()
+			.map(|variation| hb::hb_variation_t { tag: u32::from_be_bytes(variation.tag), value: variation.value })
+			.collect();
+		unsafe { hb::hb_font_set_variations(self.hfont.as_raw(), hb_variations.as_ptr(), hb_variations.len() as _) };
+
+		self.metrics = vary_metrics(&self.data, self.index, &self.variations, variations, self.metrics);
+		self.variations = variations.to_vec();
+		self.decoration_metrics = DecorationMetrics::new(&self.data, self.index, &self.variations, &self.metrics);
+		self.unload();
+	}
+
+	// like set_variations, any glyphs that were already loaded have to be loaded again, so it's meant to be called
+	// right after creating the font. it's kept but has no effect while the font has variations set, and takes effect
+	// again once they're cleared. glyphs drawn with distance fields are never hinted.
+	pub fn hinting(mut self, hinting: Hinting) -> Self {
+		self.hinting = hinting;
+		self.hinting_data = match hinting {
+			Hinting::None => None,
+			_ => self.hinting_data.take().or_else(|| Some(Arc::new(self.data.to_vec()))),
+		};
+		self.unload();
+		self
+	}
+
+	// the renderer for text that doesn't choose its own. glyphs are loaded separately for each renderer, so none have
+	// to be loaded again.
+	pub fn renderer(mut self, renderer: Renderer) -> Self {
+		self.renderer = renderer;
+		self
+	}
+
+	// unlike the other settings, this only changes how glyphs are resolved, so they don't have to be loaded again
+	pub fn fill_rule(mut self, fill_rule: FillRule) -> Self {
+		self.fill_rule = fill_rule;
+		self
+	}
+
+	// the axes of a variable font, or nothing for a static one
+	pub fn axes(&self) -> Vec<VariationAxis> {
+		let face = match ttf::Face::from_slice(&self.data, self.index) {
+			Ok(face) => face,
+			Err(_) => return vec![],
+		};
+
+		face.variation_axes()
+			.map(|axis| VariationAxis {
+				tag: axis.tag.to_bytes(),
+				name: face.names().filter(|name| name.name_id() == axis.name_id).find_map(|name| name.to_string()),
+				min: axis.min_value,
+				default: axis.def_value,
+				max: axis.max_value,
+				hidden: axis.hidden,
+			})
+			.collect()
+	}
+
+	// where tessellated glyphs are saved and loaded from, or None to always tessellate them
+	pub fn set_cache_dir(&mut self, dir: Option<PathBuf>) {
+		self.cache_dir = dir;
+	}
+
+	// loads the glyphs for the font's own renderer
+	pub fn load_block(&mut self, block: &'static str) -> Box<dyn GpuFuture + Send + Sync> {
+		let renderer = self.renderer;
+		let glyphs = self.block_glyphs(block, renderer);
+		let (glyphs, images) = self.unrequested_glyphs(glyphs, renderer);
+		if glyphs.is_empty() {
+			return Box::new(sync::now(self.queue.device().clone()));
+		}
+
+		let recv = load_font(self, glyphs, images, renderer);
+		match block_on(recv.recv()) {
+			Ok(loaded) => self.add_page(loaded),
+			// the loading thread gave up on these glyphs, so they stay placeholders
+			Err(_) => Box::new(sync::now(self.queue.device().clone())),
+		}
+	}
+
+	pub fn request_block(&mut self, block: &'static str, renderer: Renderer) {
+		let glyphs = self.block_glyphs(block, renderer);
+		self.request_glyphs(glyphs, renderer);
+	}
+
+	pub fn request_glyphs(&mut self, glyphs: impl IntoIterator<Item = u32>, renderer: Renderer) {
+		let (glyphs, images) = self.unrequested_glyphs(glyphs, renderer);
+		if !glyphs.is_empty() {
+			self.loading.push(load_font(self, glyphs, images, renderer));
+		}
+	}
+
+	pub fn update(&mut self) -> Option<Box<dyn GpuFuture + Send + Sync>> {
+		let mut future = self.upload_future.take();
+
+		let mut i = 0;
+		while i < self.loading.len() {
+			match self.loading[i].try_recv() {
+				Ok(loaded) => {
+					self.loading.swap_remove(i);
+					let page_future = self.add_page(loaded);
+					future = Some(match future {
+						Some(future) => Box::new(future.join(page_future)),
+						None => page_future,
+					});
+				},
+				// the loading thread gave up on these glyphs
+				Err(TryRecvError::Closed) => {
+					self.loading.swap_remove(i);
+				},
+				Err(TryRecvError::Empty) => i += 1,
+			}
+		}
+
+		future
+	}
+
+	// the number of CPAL palettes that a color font's glyphs can be drawn with
+	pub fn palette_count(&self) -> usize {
+		unsafe { hb::hb_ot_color_palette_get_count(self.hface()) as _ }
+	}
+
+	fn hface(&self) -> *mut hb::hb_face_t {
+		unsafe { hb::hb_font_get_face(self.hfont.as_raw()) }
+	}
+
+	// the COLR layers of a color glyph from bottom to top, as glyphs and palette entries. other glyphs have none.
+	fn color_layers(&self, glyph: u32) -> Vec<(u32, u32)> {
+		unsafe {
+			let face = self.hface();
+			let mut count = hb::hb_ot_color_glyph_get_layers(face, glyph, 0, ptr::null_mut(), ptr::null_mut());
+			let mut layers: Vec<_> = (0..count).map(|_| hb::hb_ot_color_layer_t { glyph: 0, color_index: 0 }).collect();
+			hb::hb_ot_color_glyph_get_layers(face, glyph, 0, &mut count, layers.as_mut_ptr());
+			layers.iter().take(count as _).map(|layer| (layer.glyph, layer.color_index)).collect()
+		}
+	}
+
+	// palette entry 0xffff, or an entry the palette doesn't have, is the text's own color
+	fn palette_color(&self, palette: usize, index: u32, foreground: [f32; 4]) -> [f32; 4] {
+		if index == 0xffff {
+			return foreground;
+		}
+
+		let mut color = 0;
+		let mut count = 1;
+		unsafe { hb::hb_ot_color_palette_get_colors(self.hface(), palette as _, index, &mut count, &mut color) };
+		if count == 0 {
+			return foreground;
 		}
+
+		// hb_color_t is bgra, with blue in the highest byte
+		let [b, g, r, a] = color.to_be_bytes();
+		[r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0 * foreground[3]]
+	}
+
+	// the corners of a glyph's outline in font units, with y pointing down
+	fn glyph_extents(&self, glyph: u32) -> Option<(Vector2<f32>, Vector2<f32>)> {
+		let mut extents = hb::hb_glyph_extents_t { x_bearing: 0, y_bearing: 0, width: 0, height: 0 };
+		if unsafe { hb::hb_font_get_glyph_extents(self.hfont.as_raw(), glyph, &mut extents) } == 0 {
+			return None;
+		}
+		if extents.width == 0 || extents.height == 0 {
+			return None;
+		}
+
+		let hb::hb_glyph_extents_t { x_bearing, y_bearing, width, height } = extents;
+		let min = Vector2::new(x_bearing as f32, -y_bearing as f32);
+		Some((min, min + Vector2::new(width as f32, -height as f32)))
+	}
+
+	// the hinting glyphs drawn with a renderer are actually loaded with. outlines from ttf-parser and distance fields
+	// are never hinted.
+	fn hinted(&self, renderer: Renderer) -> Hinting {
+		if !self.variations.is_empty() || renderer == Renderer::Sdf {
+			return Hinting::None;
+		}
+		self.hinting
 	}
 
-	pub fn load_block(&mut self, queue: &Arc<Queue>, block: &'static str) -> impl GpuFuture {
-		let (block_info, glyph_info, future) = block_on(load_font(block, queue));
-		self.block_info.insert(block, block_info);
-		self.glyph_info.extend(glyph_info);
+	fn fill_flag(&self) -> f32 {
+		match self.fill_rule {
+			FillRule::NonZero => FILL_NONZERO,
+			FillRule::EvenOdd => FILL_EVEN_ODD,
+		}
+	}
+
+	// forgets every loaded glyph, so they're loaded again with the font's new settings
+	fn unload(&mut self) {
+		self.pages.truncate(1);
+		self.loading.clear();
+		self.glyph_info.clear();
+		self.atlases.clear();
+		self.bitmaps.clear();
+		self.sdf_glyphs.clear();
+		self.requested_blocks.clear();
+		self.requested_glyphs.clear();
+	}
+
+	fn add_page(&mut self, loaded: LoadFontReturn) -> Box<dyn GpuFuture + Send + Sync> {
+		// glyphs loaded without a page have no windings, so their empty draws can use any page
+		let page_index = match loaded.page {
+			Some(page) => {
+				self.pages.push(page);
+				self.pages.len() - 1
+			},
+			None => 0,
+		};
+		for (glyph_id, mut glyph) in loaded.glyphs {
+			glyph.page = page_index;
+			self.glyph_info.insert(glyph_id, glyph);
+		}
+
+		let mut future = loaded.future;
+		if let Some(atlas) = loaded.atlas {
+			future = Box::new(future.join(self.add_atlas(atlas, false)));
+		}
+		if let Some(atlas) = loaded.sdf {
+			future = Box::new(future.join(self.add_atlas(atlas, true)));
+		}
 		future
 	}
 
-	pub fn draw(
-		&self,
-		text: &str,
-	) -> (
-		Arc<CpuBufferPoolChunk<DrawIndexedIndirectCommand, Arc<StdMemoryPool>>>,
-		Arc<CpuBufferPoolChunk<DrawIndirectCommand, Arc<StdMemoryPool>>>,
-		Arc<CpuBufferPoolChunk<ChInstance, Arc<StdMemoryPool>>>,
-	) {
-		let hfont = HFont::new(Face::from_file("res/Roboto-Regular.ttf", 0).unwrap());
-		let buffer = UnicodeBuffer::new().add_str(text);
-		let output = shape(&hfont, buffer, &[]);
-		let positions = output.get_glyph_positions();
-		let infos = output.get_glyph_infos();
-
-		let cmds = (0..infos.len() * 6).map(|i| {
-			let GlyphInfo { codepoint, .. } = infos[i / 6];
-			let GlyphInfo2 { index_count, first_index, vertex_offset, .. } = *self.glyph_info.get(&codepoint).unwrap();
-			DrawIndexedIndirectCommand {
-				index_count,
-				instance_count: 1,
-				first_index,
-				vertex_offset,
-				first_instance: (i / 6) as _,
+	fn add_atlas(&mut self, atlas: Atlas, sdf: bool) -> Box<dyn GpuFuture + Send + Sync> {
+		let atlas_index = self.atlases.len();
+		self.atlases.push(atlas.image);
+		let glyphs = if sdf { &mut self.sdf_glyphs } else { &mut self.bitmaps };
+		for (glyph_id, mut bitmap) in atlas.glyphs {
+			bitmap.page = atlas_index;
+			glyphs.insert(glyph_id, bitmap);
+		}
+		atlas.future
+	}
+
+	// the glyphs that weren't requested for this renderer yet, and the ones among them that weren't requested for the
+	// other one either, whose embedded images haven't been loaded
+	fn unrequested_glyphs(
+		&mut self,
+		glyphs: impl IntoIterator<Item = u32>,
+		renderer: Renderer,
+	) -> (Vec<u32>, Vec<u32>) {
+		// .notdef is drawn with the placeholder
+		let glyphs: Vec<_> =
+			glyphs.into_iter().filter(|&glyph| glyph != 0 && self.requested_glyphs.insert((glyph, renderer))).collect();
+		let other = match renderer {
+			Renderer::Winding => Renderer::Sdf,
+			Renderer::Sdf => Renderer::Winding,
+		};
+		let images = glyphs.iter().copied().filter(|&glyph| !self.requested_glyphs.contains(&(glyph, other))).collect();
+		(glyphs, images)
+	}
+
+	// the glyphs for a block's characters, plus everything GSUB can substitute them with
+	fn block_glyphs(&mut self, block: &'static str, renderer: Renderer) -> Vec<u32> {
+		if !self.requested_blocks.insert((block, renderer)) {
+			return vec![];
+		}
+
+		let chars: Vec<u32> = BLOCKS[block].into_iter().map(|ch| ch as u32).collect();
+		let mut glyphs = vec![];
+		unsafe {
+			let buffer = hb::hb_buffer_create();
+			hb::hb_buffer_add_utf32(buffer, chars.as_ptr(), chars.len() as _, 0, chars.len() as _);
+			hb::hb_buffer_guess_segment_properties(buffer);
+
+			let set = hb::hb_set_create();
+			hb::hb_ot_shape_glyphs_closure(self.hfont.as_raw(), buffer, ptr::null(), 0, set);
+			let mut glyph = u32::MAX;
+			while hb::hb_set_next(set, &mut glyph) != 0 {
+				glyphs.push(glyph);
 			}
-		});
-		let cmds = self.cmd_pool.chunk(cmds).unwrap();
-
-		let qcmds = (0..infos.len() * 6).map(|i| {
-			let GlyphInfo { codepoint, .. } = infos[i / 6];
-			let GlyphInfo2 { qvertex_count, qvertex_offset, .. } = *self.glyph_info.get(&codepoint).unwrap();
-			DrawIndirectCommand {
-				vertex_count: qvertex_count,
-				instance_count: 1,
-				first_vertex: qvertex_offset,
-				first_instance: (i / 6) as _,
+
+			hb::hb_set_destroy(set);
+			hb::hb_buffer_destroy(buffer);
+		}
+
+		glyphs
+	}
+}
+
+// identifies the data, face index, variations and hinting in the glyph cache
+fn font_hash(data_hash: u64, index: u32, variations: &[Variation], hinting: Hinting, renderer: Renderer) -> u64 {
+	let mut hash_data = data_hash.to_le_bytes().to_vec();
+	hash_data.extend_from_slice(&index.to_le_bytes());
+	for variation in variations {
+		hash_data.extend_from_slice(&variation.tag);
+		hash_data.extend_from_slice(&variation.value.to_le_bytes());
+	}
+	// unhinted outlines are the same at every size, so they share a cache file
+	match hinting {
+		Hinting::None => (),
+		Hinting::Vertical(size) => {
+			hash_data.push(b'v');
+			hash_data.extend_from_slice(&size.to_le_bytes());
+		},
+		Hinting::Full(size) => {
+			hash_data.push(b'f');
+			hash_data.extend_from_slice(&size.to_le_bytes());
+		},
+	}
+	// distance fields get their own file
+	if renderer == Renderer::Sdf {
+		hash_data.extend_from_slice(b"sdf");
+	}
+	cache::hash(&hash_data)
+}
+
+// the same metrics FreeType reads, which prefers hhea's ascender and descender over OS/2's
+fn face_metrics(face: &ttf::Face) -> Metrics {
+	let hhea = face.table_data(ttf::Tag::from_bytes(b"hhea")).filter(|hhea| hhea.len() >= 10);
+	let hhea_field = |offset: usize| hhea.map_or(0, |hhea| i16::from_be_bytes([hhea[offset], hhea[offset + 1]]));
+	let (ascent, descent, line_gap) = match (hhea_field(4), hhea_field(6)) {
+		(0, 0) => (face.ascender(), face.descender(), face.line_gap()),
+		(ascent, descent) => (ascent, descent, hhea_field(8)),
+	};
+	let underline = face.underline_metrics();
+	let bbox = face.global_bounding_box();
+	Metrics {
+		units_per_em: units_per_em(face),
+		ascent: ascent as f32,
+		descent: descent as f32,
+		line_gap: line_gap as f32,
+		underline_position: underline.map_or(0.0, |line| line.position as f32 + line.thickness as f32 / 2.0),
+		underline_thickness: underline.map_or(0.0, |line| line.thickness as f32),
+		cap_height: face.capital_height().unwrap_or(0) as f32,
+		x_height: face.x_height().unwrap_or(0) as f32,
+		bounding_box: RectF::from_points(
+			Vector2F::new(bbox.x_min as f32, bbox.y_min as f32),
+			Vector2F::new(bbox.x_max as f32, bbox.y_max as f32),
+		),
+	}
+}
+
+// ttf-parser gives up on sizes outside what the spec allows, so those fall back to the most common one
+fn units_per_em(face: &ttf::Face) -> u32 {
+	face.units_per_em().map_or(1000, u32::from)
+}
+
+// hhea's metrics are always the default instance's, so the differences ttf-parser finds between the old and new
+// positions on the font's axes are added to them
+fn vary_metrics(data: &[u8], index: u32, from: &[Variation], to: &[Variation], mut metrics: Metrics) -> Metrics {
+	let face = |variations: &[Variation]| {
+		let mut face = ttf::Face::from_slice(data, index).ok()?;
+		for variation in variations {
+			face.set_variation(ttf::Tag::from_bytes(&variation.tag), variation.value);
+		}
+		Some(face)
+	};
+	let (from, to) = match (face(from), face(to)) {
+		(Some(from), Some(to)) => (from, to),
+		_ => return metrics,
+	};
+
+	let delta = |from: i16, to: i16| to as f32 - from as f32;
+	metrics.ascent += delta(from.ascender(), to.ascender());
+	metrics.descent += delta(from.descender(), to.descender());
+	metrics.line_gap += delta(from.line_gap(), to.line_gap());
+	if let (Some(from), Some(to)) = (from.capital_height(), to.capital_height()) {
+		metrics.cap_height += delta(from, to);
+	}
+	if let (Some(from), Some(to)) = (from.x_height(), to.x_height()) {
+		metrics.x_height += delta(from, to);
+	}
+	metrics
+}
+
+// where the top of each kind of decoration goes, and how thick it is, in font units with y pointing up
+#[derive(Debug, Copy, Clone)]
+struct DecorationMetrics {
+	underline: (f32, f32),
+	strikethrough: (f32, f32),
+	overline: (f32, f32),
+}
+impl DecorationMetrics {
+	fn new(data: &[u8], index: u32, variations: &[Variation], metrics: &Metrics) -> Self {
+		// ttf-parser applies the font's MVAR deltas to these
+		let face = ttf::Face::from_slice(data, index).ok().map(|mut face| {
+			for variation in variations {
+				face.set_variation(ttf::Tag::from_bytes(&variation.tag), variation.value);
 			}
+			face
 		});
-		let qcmds = self.cmd_pool2.chunk(qcmds).unwrap();
+		let line = |line: Option<ttf::LineMetrics>| {
+			line.filter(|line| line.thickness > 0).map(|line| (line.position as f32, line.thickness as f32))
+		};
+
+		// the fallbacks are close to what most fonts ask for
+		let em = metrics.units_per_em as f32;
+		let underline = face.as_ref().and_then(|face| line(face.underline_metrics())).unwrap_or((-em * 0.1, em * 0.05));
+		let x_height = if metrics.x_height > 0.0 { metrics.x_height } else { em * 0.5 };
+		let strikethrough = face
+			.as_ref()
+			.and_then(|face| line(face.strikeout_metrics()))
+			.unwrap_or(((x_height + underline.1) / 2.0, underline.1));
+		// fonts don't have overline metrics, so it's as thick as the underline, just inside the ascent
+		let overline = (metrics.ascent, underline.1);
 
-		let mut cursor = Vector2::zeros();
-		let instances = self
-			.instance_pool
-			.chunk(positions.iter().map(|pos| {
-				let GlyphPosition { x_advance, y_advance, x_offset, y_offset, .. } = *pos;
+		Self { underline, strikethrough, overline }
+	}
+}
+
+// a position on one of a variable font's axes, like wght, wdth, opsz or slnt
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Variation {
+	pub tag: [u8; 4],
+	pub value: f32,
+}
+impl Variation {
+	pub fn new(tag: &[u8; 4], value: f32) -> Self {
+		Self { tag: *tag, value }
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct VariationAxis {
+	pub tag: [u8; 4],
+	pub name: Option<String>,
+	pub min: f32,
+	pub default: f32,
+	pub max: f32,
+	// hidden axes are meant to be set by applications, not users
+	pub hidden: bool,
+}
+
+// fits outlines to the pixel grid at one size in pixels, which keeps small text sharp, but distorts it at any other
+// size. vertical hinting leaves glyphs' widths alone. fonts with variations set are never hinted, since their outlines
+// come from ttf-parser, and neither are glyphs drawn with distance fields.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Hinting {
+	None,
+	Vertical(f32),
+	Full(f32),
+}
+impl Hinting {
+	fn options(self) -> HintingOptions {
+		match self {
+			Self::None => HintingOptions::None,
+			Self::Vertical(size) => HintingOptions::Vertical(size),
+			Self::Full(size) => HintingOptions::Full(size),
+		}
+	}
+
+	// hinted outlines only line up with the pixel grid if the glyphs they're drawn at do
+	fn snap(self, point: Vector2<f32>) -> Vector2<f32> {
+		match self {
+			Self::None => point,
+			Self::Vertical(_) => Vector2::new(point.x, point.y.round()),
+			Self::Full(_) => point.map(f32::round),
+		}
+	}
+}
+
+// how a font's glyphs are drawn. windings are exact at any size, but need a pass over a buffer the size of the
+// window. distance fields are cheap to draw at any transform, but round off corners at large sizes, and don't get a
+// text's stroke, shadow or glow, since those are grown from the windings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Renderer {
+	Winding,
+	Sdf,
+}
+
+// how overlapping contours are filled. TrueType and CFF outlines are meant to be filled with nonzero, but some older
+// fonts expect even-odd.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FillRule {
+	NonZero,
+	EvenOdd,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+pub struct Fonts {
+	fonts: Vec<Font>,
+	cmd_pool: CpuBufferPool<DrawIndexedIndirectCommand>,
+	cmd_pool2: CpuBufferPool<DrawIndirectCommand>,
+	instance_pool: CpuBufferPool<ChInstance>,
+	bitmap_pool: CpuBufferPool<BitmapInstance>,
+	// counts the times any font could have been changed, so layouts made before then are made again
+	revision: u64,
+}
+impl Fonts {
+	pub fn new(queue: &Arc<Queue>) -> Self {
+		Self {
+			fonts: vec![],
+			cmd_pool: CpuBufferPool::indirect_buffer(queue.device().clone()),
+			cmd_pool2: CpuBufferPool::indirect_buffer(queue.device().clone()),
+			instance_pool: CpuBufferPool::vertex_buffer(queue.device().clone()),
+			bitmap_pool: CpuBufferPool::vertex_buffer(queue.device().clone()),
+			revision: 0,
+		}
+	}
+
+	pub fn add(&mut self, font: Font) -> FontId {
+		self.fonts.push(font);
+		FontId(self.fonts.len() - 1)
+	}
+
+	pub fn get(&self, id: FontId) -> &Font {
+		&self.fonts[id.0]
+	}
+
+	pub fn get_mut(&mut self, id: FontId) -> &mut Font {
+		self.revision += 1;
+		&mut self.fonts[id.0]
+	}
+
+	pub fn update(&mut self) -> Option<Box<dyn GpuFuture + Send + Sync>> {
+		let futures = self.fonts.iter_mut().filter_map(Font::update);
+		futures.reduce(|a, b| Box::new(a.join(b)))
+	}
+
+	// uses the same layout as drawing, so the results always match what's drawn
+	pub fn measure(&self, font: FontId, text: &str, size: f32, options: &LayoutOptions) -> TextMetrics {
+		Layout::new(self, &RichText::plain(text, TextStyle::new(font, size)), options).measure(self)
+	}
+
+	// rounds where a layout is drawn to whole pixels along the axes any of its fonts are hinted on
+	pub fn snap(&self, layout: &Layout, origin: Vector2<f32>) -> Vector2<f32> {
+		layout
+			.spans
+			.iter()
+			.fold(origin, |origin, (_, style)| self.fonts[style.font.0].hinted(self.renderer(style)).snap(origin))
+	}
+
+	// the renderer a span is drawn with
+	fn renderer(&self, style: &TextStyle) -> Renderer {
+		style.renderer.unwrap_or(self.fonts[style.font.0].renderer)
+	}
+
+	pub fn draw(&mut self, text: &RichText, options: &LayoutOptions) -> Vec<TextDraw> {
+		let layout = Layout::new(self, text, options);
+		self.draw_layout(&layout, false)
+	}
+
+	// snapping rounds the glyphs of hinted fonts to whole pixels, which only lines them up with the pixel grid for text
+	// laid out in pixels and drawn at an origin snapped with Fonts::snap, so it's left off for anything else, like
+	// labels, which are laid out in world units.
+	pub fn draw_layout(&mut self, layout: &Layout, snap: bool) -> Vec<TextDraw> {
+		for (range, style) in &layout.spans {
+			let renderer = self.renderer(style);
+			let font = &mut self.fonts[style.font.0];
+			for ch in layout.text[range.clone()].chars() {
+				if let Some(block) = Block::of(ch) {
+					font.request_block(block.name, renderer);
+				}
+			}
+		}
+
+		if layout.glyphs.is_empty() {
+			return vec![];
+		}
+
+		// color glyphs are replaced by their layers, which are drawn one at a time in their palette colors
+		let mut entries = vec![];
+		// glyphs drawn with distance fields are drawn with windings too until their fields are loaded, so they still
+		// get placeholders
+		for glyph in &layout.glyphs {
+			let style = &layout.spans[glyph.span].1;
+			let renderer = self.renderer(style);
+			let font = &self.fonts[style.font.0];
+			let sdf = renderer == Renderer::Sdf && font.sdf_glyphs.contains_key(&glyph.id);
+			if font.bitmaps.contains_key(&glyph.id) || sdf {
+				continue;
+			}
+			let scale = style.size / font.metrics.units_per_em as f32;
+			let ch_pos = if snap { font.hinted(renderer).snap(glyph.position) } else { glyph.position }.into();
+			let ch_fill = font.fill_flag();
+			let instance = |ch_color| ChInstance { ch_pos, ch_scale: [scale; 2], ch_color, ch_fill };
+			let layers = font.color_layers(glyph.id);
+			if layers.is_empty() {
+				entries.push((0, style.font, renderer, Some(glyph.id), instance(style.color)));
+			}
+			for (i, (id, color_index)) in layers.into_iter().enumerate() {
+				let color = font.palette_color(style.palette, color_index, style.color);
+				entries.push((i + 1, style.font, renderer, Some(id), instance(color)));
+			}
+		}
+
+		// decorations are stretched squares, so they're covered the same way glyphs are. they're always resolved with
+		// nonzero, since with even-odd a line through a stem would cut a hole in it.
+		for decoration in layout.decorations(self) {
+			let style = &layout.spans[decoration.span].1;
+			entries.push((0, style.font, Renderer::Winding, None, ChInstance {
+				ch_pos: [decoration.min.x, decoration.max.y],
+				ch_scale: (decoration.max - decoration.min).into(),
+				ch_color: style.color,
+				ch_fill: FILL_NONZERO,
+			}));
+		}
+
+		// the block closures cover nearly everything, but shaping can still reach glyphs that aren't in them
+		for &(_, font, renderer, id, _) in &entries {
+			self.fonts[font.0].request_glyphs(id, renderer);
+		}
+
+		let instances = entries.iter().map(|&(.., instance)| instance);
+		let instances = Arc::new(self.instance_pool.chunk(instances).unwrap());
+
+		// glyphs that aren't loaded yet, including ones the font doesn't have, are drawn as placeholder boxes
+		let mut batches: HashMap<(usize, FontId, usize), Vec<(usize, &GlyphInfo2)>> = HashMap::new();
+		for (i, &(layer, font_id, _, id, _)) in entries.iter().enumerate() {
+			let font = &self.fonts[font_id.0];
+			let glyph = match id {
+				Some(id) => font.glyph_info.get(&id).unwrap_or(&font.placeholder_glyph),
+				None => &font.square_glyph,
+			};
+			batches.entry((layer, font_id, glyph.page)).or_default().push((i, glyph));
+		}
+
+		batches
+			.into_iter()
+			.map(|((layer, font, page), glyphs)| {
+				let cmds = (0..glyphs.len() * 6).map(|i| {
+					let (instance, glyph) = glyphs[i / 6];
+					DrawIndexedIndirectCommand {
+						index_count: glyph.index_count,
+						instance_count: 1,
+						first_index: glyph.first_index,
+						vertex_offset: glyph.vertex_offset,
+						first_instance: instance as _,
+					}
+				});
+				let cmds = self.cmd_pool.chunk(cmds).unwrap();
+
+				let qcmds = (0..glyphs.len() * 6).map(|i| {
+					let (instance, glyph) = glyphs[i / 6];
+					DrawIndirectCommand {
+						vertex_count: glyph.qvertex_count,
+						instance_count: 1,
+						first_vertex: glyph.qvertex_offset,
+						first_instance: instance as _,
+					}
+				});
+				let qcmds = self.cmd_pool2.chunk(qcmds).unwrap();
+
+				let page = self.fonts[font.0].pages[page].clone();
+				TextDraw { layer, page, cmds: Arc::new(cmds), qcmds: Arc::new(qcmds), instances: instances.clone() }
+			})
+			.collect()
+	}
+
+	// glyphs with embedded images, like the emoji in CBDT and sbix fonts, and the glyphs of fonts that use distance
+	// fields, are drawn from atlases by this instead of by draw_layout. they go on top of the color glyph layers.
+	pub fn draw_bitmaps(&mut self, layout: &Layout) -> Vec<BitmapDraw> {
+		let mut batches: HashMap<(FontId, usize, bool), Vec<BitmapInstance>> = HashMap::new();
+		for glyph in &layout.glyphs {
+			let style = &layout.spans[glyph.span].1;
+			let font = &self.fonts[style.font.0];
+			let sdf_glyph = match self.renderer(style) {
+				Renderer::Winding => None,
+				Renderer::Sdf => font.sdf_glyphs.get(&glyph.id),
+			};
+			// images keep their own colors, and are only faded by the text's alpha
+			let (bitmap, sdf, color) = match (font.bitmaps.get(&glyph.id), sdf_glyph) {
+				(Some(bitmap), _) => (bitmap, false, [1.0, 1.0, 1.0, style.color[3]]),
+				(None, Some(bitmap)) => (bitmap, true, style.color),
+				(None, None) => continue,
+			};
+
+			let scale = style.size / bitmap.ppem;
+			batches.entry((style.font, bitmap.page, sdf)).or_default().push(BitmapInstance {
+				bm_pos: (glyph.position + bitmap.offset * scale).into(),
+				bm_size: (bitmap.size * scale).into(),
+				bm_uv: bitmap.uv.into(),
+				bm_uv_size: bitmap.uv_size.into(),
+				bm_color: color,
+			});
+		}
+
+		batches
+			.into_iter()
+			.map(|((font, page, sdf), instances)| BitmapDraw {
+				sdf,
+				atlas: self.fonts[font.0].atlases[page].clone(),
+				instances: Arc::new(self.bitmap_pool.chunk(instances).unwrap()),
+			})
+			.collect()
+	}
+}
+
+pub struct Text {
+	pub content: String,
+	// in pixels, from the top left corner of the window
+	pub position: Vector2<f32>,
+	pub anchor: Anchor,
+	pub font: FontId,
+	// pixels per em
+	pub size: f32,
+	pub color: [f32; 4],
+	pub underline: bool,
+	pub strikethrough: bool,
+	// fills the text's bounds
+	pub background: Option<[f32; 4]>,
+	// overrides the font's renderer
+	pub renderer: Option<Renderer>,
+	// drawn under the text, in the order shadow, glow, stroke. glyph images and glyphs drawn with Renderer::Sdf don't
+	// get them.
+	pub stroke: Option<Stroke>,
+	pub shadow: Option<Shadow>,
+	pub glow: Option<Glow>,
+	layout: LayoutCache,
+}
+impl Text {
+	pub fn new(content: impl Into<String>, font: FontId, size: f32) -> Self {
+		Self {
+			content: content.into(),
+			position: Vector2::zeros(),
+			anchor: Anchor::TopLeft,
+			font,
+			size,
+			color: [1.0, 1.0, 1.0, 1.0],
+			underline: false,
+			strikethrough: false,
+			renderer: None,
+			background: None,
+			stroke: None,
+			shadow: None,
+			glow: None,
+			layout: LayoutCache::default(),
+		}
+	}
+
+	pub fn layout(&mut self, fonts: &Fonts) -> Arc<Layout> {
+		let style = TextStyle::new(self.font, self.size)
+			.color(self.color)
+			.underline(self.underline)
+			.strikethrough(self.strikethrough)
+			.renderer(self.renderer);
+		self.layout.get(fonts, RichText::plain(self.content.clone(), style))
+	}
+
+	// where the layout's origin goes, in pixels
+	pub fn origin(&self, layout: &Layout) -> Vector2<f32> {
+		self.position - self.anchor.point(layout)
+	}
+}
+
+// widths, offsets and radii are in pixels. they're limited by how far the effects shader samples, so very large ones
+// get blocky.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stroke {
+	pub width: f32,
+	pub color: [f32; 4],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shadow {
+	// y points down
+	pub offset: Vector2<f32>,
+	pub blur: f32,
+	pub color: [f32; 4],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glow {
+	pub radius: f32,
+	pub color: [f32; 4],
+}
+
+// text placed in the world and drawn through the camera
+pub struct Label {
+	pub content: String,
+	pub position: Vector3<f32>,
+	// none keeps the label facing the camera
+	pub rotation: Option<Quaternion<f32>>,
+	pub anchor: Anchor,
+	pub font: FontId,
+	// world units per em
+	pub size: f32,
+	pub color: [f32; 4],
+	// overrides the font's renderer. distance fields stay sharp when labels are scaled or seen at an angle.
+	pub renderer: Option<Renderer>,
+	layout: LayoutCache,
+}
+impl Label {
+	pub fn new(content: impl Into<String>, font: FontId, size: f32) -> Self {
+		Self {
+			content: content.into(),
+			position: Vector3::zeros(),
+			rotation: None,
+			anchor: Anchor::Center,
+			font,
+			size,
+			color: [1.0, 1.0, 1.0, 1.0],
+			renderer: None,
+			layout: LayoutCache::default(),
+		}
+	}
+
+	pub fn layout(&mut self, fonts: &Fonts) -> Arc<Layout> {
+		let style = TextStyle::new(self.font, self.size).color(self.color).renderer(self.renderer);
+		self.layout.get(fonts, RichText::plain(self.content.clone(), style))
+	}
 
-				let position = cursor + Vector2::new(x_offset as _, y_offset as _);
-				cursor += Vector2::new(x_advance as _, y_advance as _);
+	// where the layout's origin goes, relative to the label's position
+	pub fn origin(&self, layout: &Layout) -> Vector2<f32> {
+		-self.anchor.point(layout)
+	}
+}
 
-				assert_eq!(position.y, 0.0);
+// texts and labels are drawn every frame, but rarely change, so they keep their last layout, and only shape their text
+// again once it, its style or the fonts have changed
+#[derive(Default)]
+struct LayoutCache(Option<(RichText, u64, Arc<Layout>)>);
+impl LayoutCache {
+	fn get(&mut self, fonts: &Fonts, text: RichText) -> Arc<Layout> {
+		match &self.0 {
+			Some((cached, revision, layout)) if *cached == text && *revision == fonts.revision => layout.clone(),
+			_ => {
+				let layout = Arc::new(Layout::new(fonts, &text, &LayoutOptions::default()));
+				self.0 = Some((text, fonts.revision, layout.clone()));
+				layout
+			},
+		}
+	}
+}
 
-				ChInstance { ch_pos: position.into() }
-			}))
-			.unwrap();
+// which point of the text's bounds sits at its position. the baseline is the first line's.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Anchor {
+	TopLeft,
+	Top,
+	TopRight,
+	Left,
+	Center,
+	Right,
+	BottomLeft,
+	Bottom,
+	BottomRight,
+	BaselineLeft,
+	Baseline,
+	BaselineRight,
+}
+impl Anchor {
+	fn point(self, layout: &Layout) -> Vector2<f32> {
+		use Anchor::*;
 
-		(Arc::new(cmds), Arc::new(qcmds), Arc::new(instances))
+		let (min, max) = layout.bounds();
+		let x = match self {
+			TopLeft | Left | BottomLeft | BaselineLeft => min.x,
+			Top | Center | Bottom | Baseline => (min.x + max.x) / 2.0,
+			TopRight | Right | BottomRight | BaselineRight => max.x,
+		};
+		let y = match self {
+			TopLeft | Top | TopRight => min.y,
+			Left | Center | Right => (min.y + max.y) / 2.0,
+			BottomLeft | Bottom | BottomRight => max.y,
+			BaselineLeft | Baseline | BaselineRight => 0.0,
+		};
+		Vector2::new(x, y)
 	}
 }
 
+pub struct TextDraw {
+	// 0 for ordinary glyphs, and 1 and up for the layers of color glyphs, from the bottom
+	pub layer: usize,
+	pub page: GlyphPage,
+	pub cmds: Arc<CpuBufferPoolChunk<DrawIndexedIndirectCommand, Arc<StdMemoryPool>>>,
+	pub qcmds: Arc<CpuBufferPoolChunk<DrawIndirectCommand, Arc<StdMemoryPool>>>,
+	pub instances: Arc<CpuBufferPoolChunk<ChInstance, Arc<StdMemoryPool>>>,
+}
+
+pub struct BitmapDraw {
+	// whether the atlas holds distance fields instead of images
+	pub sdf: bool,
+	pub atlas: AtlasImage,
+	pub instances: Arc<CpuBufferPoolChunk<BitmapInstance, Arc<StdMemoryPool>>>,
+}
+
+// maximum distance between a cubic curve and the quadratic curves that replace it, in font units
+const CUBIC_TOLERANCE: f32 = 0.2;
+
 #[derive(Debug)]
 struct TriangleBuilder {
 	pen: Vector2F,
@@ -201,6 +1289,18 @@ impl TriangleBuilder {
 		self.verts.push(TriangleVertex { v_pos: [0.0, 0.0] });
 		self.verts.push(TriangleVertex { v_pos: [0.0, 0.0] });
 	}
+
+	fn rect(&mut self, min: Vector2F, max: Vector2F) {
+		self.move_to(min);
+		self.line_to(Vector2F::new(max.x(), min.y()));
+		self.line_to(max);
+		self.line_to(Vector2F::new(min.x(), max.y()));
+		self.close();
+	}
+
+	fn finish(self, glyphs: Vec<(u32, GlyphInfo2)>) -> GlyphGeometry {
+		GlyphGeometry { verts: self.verts, indices: self.indices, qverts: self.qverts, glyphs }
+	}
 }
 impl OutlineSink for TriangleBuilder {
 	fn move_to(&mut self, to: Vector2F) {
@@ -239,12 +1339,21 @@ impl OutlineSink for TriangleBuilder {
 	}
 
 	fn cubic_curve_to(&mut self, ctrl: LineSegment2F, to: Vector2F) {
-		let index = (self.verts.len() - self.offset) as u16;
-		self.verts.push(TriangleVertex { v_pos: [to.x(), -to.y()] });
-		self.indices.push(0);
-		self.indices.push(index - 1);
-		self.indices.push(index);
-		self.pen = to;
+		let cubic = [self.pen, ctrl.from(), ctrl.to(), to];
+
+		// the error of the midpoint quadratic approximation shrinks with the cube of the number of pieces
+		let error = (cubic[3] - cubic[2] * 3.0 + cubic[1] * 3.0 - cubic[0]).length() * 3f32.sqrt() / 36.0;
+		let count = (error / CUBIC_TOLERANCE).cbrt().ceil().max(1.0) as u32;
+
+		for i in 0..count {
+			let t0 = i as f32 / count as f32;
+			let t1 = (i + 1) as f32 / count as f32;
+			let from = cubic_point(&cubic, t0);
+			let to = cubic_point(&cubic, t1);
+			let ctrl1 = from + cubic_tangent(&cubic, t0) * ((t1 - t0) / 3.0);
+			let ctrl2 = to - cubic_tangent(&cubic, t1) * ((t1 - t0) / 3.0);
+			self.quadratic_curve_to(((ctrl1 + ctrl2) * 3.0 - from - to) * 0.25, to);
+		}
 	}
 
 	fn close(&mut self) {
@@ -256,13 +1365,51 @@ impl OutlineSink for TriangleBuilder {
 	}
 }
 
-pub struct BlockInfo {
+// forwards ttf-parser's outlines to font-kit's sinks
+struct VariedSink<'a, S>(&'a mut S);
+impl<S: OutlineSink> ttf::OutlineBuilder for VariedSink<'_, S> {
+	fn move_to(&mut self, x: f32, y: f32) {
+		self.0.move_to(Vector2F::new(x, y));
+	}
+
+	fn line_to(&mut self, x: f32, y: f32) {
+		self.0.line_to(Vector2F::new(x, y));
+	}
+
+	fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+		self.0.quadratic_curve_to(Vector2F::new(x1, y1), Vector2F::new(x, y));
+	}
+
+	fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+		let ctrl = LineSegment2F::new(Vector2F::new(x1, y1), Vector2F::new(x2, y2));
+		self.0.cubic_curve_to(ctrl, Vector2F::new(x, y));
+	}
+
+	fn close(&mut self) {
+		self.0.close();
+	}
+}
+
+fn cubic_point(cubic: &[Vector2F; 4], t: f32) -> Vector2F {
+	let u = 1.0 - t;
+	cubic[0] * (u * u * u) + cubic[1] * (3.0 * u * u * t) + cubic[2] * (3.0 * u * t * t) + cubic[3] * (t * t * t)
+}
+
+fn cubic_tangent(cubic: &[Vector2F; 4], t: f32) -> Vector2F {
+	let u = 1.0 - t;
+	((cubic[1] - cubic[0]) * (u * u) + (cubic[2] - cubic[1]) * (2.0 * u * t) + (cubic[3] - cubic[2]) * (t * t)) * 3.0
+}
+
+#[derive(Clone)]
+pub struct GlyphPage {
 	pub verts: Arc<ImmutableBuffer<[TriangleVertex]>>,
 	pub indices: Arc<ImmutableBuffer<[u16]>>,
 	pub qverts: Arc<ImmutableBuffer<[TriangleVertex]>>,
 }
 
+#[derive(Debug, Default, PartialEq)]
 struct GlyphInfo2 {
+	page: usize,
 	index_count: u32,
 	first_index: u32,
 	vertex_offset: u32,
@@ -273,5 +1420,26 @@ struct GlyphInfo2 {
 #[derive(Default, Copy, Clone)]
 pub struct ChInstance {
 	ch_pos: [f32; 2],
+	// pixels per font unit along x and y, which only differ for decorations
+	ch_scale: [f32; 2],
+	ch_color: [f32; 4],
+	// which fill rule the pixels it touches are resolved with. a pixel keeps the largest one drawn to it.
+	ch_fill: f32,
+}
+vulkano::impl_vertex!(ChInstance, ch_pos, ch_scale, ch_color, ch_fill);
+
+// nonzero is the larger flag, so it wins wherever a nonzero glyph or a decoration overlaps an even-odd glyph. the
+// other way around, a nonzero font's overlapping contours would cut holes in it.
+const FILL_EVEN_ODD: f32 = 0.0;
+const FILL_NONZERO: f32 = 1.0;
+
+#[derive(Default, Copy, Clone)]
+pub struct BitmapInstance {
+	// pixels, relative to the layout's origin
+	bm_pos: [f32; 2],
+	bm_size: [f32; 2],
+	bm_uv: [f32; 2],
+	bm_uv_size: [f32; 2],
+	bm_color: [f32; 4],
 }
-vulkano::impl_vertex!(ChInstance, ch_pos);
+vulkano::impl_vertex!(BitmapInstance, bm_pos, bm_size, bm_uv, bm_uv_size, bm_color);
@@ -0,0 +1,116 @@
+use image::{GenericImage, ImageBuffer, ImageFormat, Pixel};
+use nalgebra::Vector2;
+use rectangle_pack::{
+	contains_smallest_box, pack_rects, volume_heuristic, GroupedRectsToPlace, RectToInsert, TargetBin,
+};
+use std::{collections::BTreeMap, sync::Arc};
+use ttf_parser as ttf;
+use vulkano::{
+	device::Queue,
+	format::Format,
+	image::{view::ImageView, ImageDimensions, ImmutableImage, MipmapsCount},
+	sync::GpuFuture,
+};
+
+// the strike closest to this size is loaded, and scaled to the size the text is drawn at
+const STRIKE_PPEM: u16 = 128;
+// keeps linear filtering from sampling the neighbouring glyphs
+const PADDING: u32 = 1;
+const MIN_ATLAS_SIZE: u32 = 256;
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+pub type AtlasImage = Arc<ImageView<Arc<ImmutableImage>>>;
+
+// where a glyph's image or distance field is in an atlas, and where it goes relative to the glyph's origin
+pub(super) struct BitmapGlyph {
+	pub page: usize,
+	// pixels at ppem, with y pointing down
+	pub offset: Vector2<f32>,
+	pub size: Vector2<f32>,
+	pub uv: Vector2<f32>,
+	pub uv_size: Vector2<f32>,
+	pub ppem: f32,
+}
+
+// a glyph, its image, the corner of the image relative to the glyph's origin with y pointing down, and the pixels per
+// em the image was made at
+pub(super) type GlyphImage<P> = (u32, ImageBuffer<P, Vec<u8>>, Vector2<f32>, f32);
+
+pub(super) struct Atlas {
+	pub image: AtlasImage,
+	pub glyphs: Vec<(u32, BitmapGlyph)>,
+	pub future: Box<dyn GpuFuture + Send + Sync>,
+}
+
+// decodes the sbix or CBDT images of any of the glyphs that have them, and packs them into one atlas
+pub(super) fn load_atlas(data: &[u8], index: u32, glyphs: &[u32], queue: &Arc<Queue>) -> Option<Atlas> {
+	let face = ttf::Face::from_slice(data, index).ok()?;
+	let images = glyphs
+		.iter()
+		.filter_map(|&glyph| {
+			// png is the only format ttf-parser returns
+			let raster = face.glyph_raster_image(ttf::GlyphId(glyph as _), STRIKE_PPEM)?;
+			let image = image::load_from_memory_with_format(raster.data, ImageFormat::Png).ok()?.to_rgba8();
+
+			// the image's corner is its bottom left, with y pointing up
+			let offset = Vector2::new(raster.x as f32, -(raster.y as f32) - image.height() as f32);
+			Some((glyph, image, offset, raster.pixels_per_em as f32))
+		})
+		.collect();
+	pack(images, Format::R8G8B8A8Unorm, queue)
+}
+
+pub(super) fn pack<P: Pixel<Subpixel = u8> + 'static>(
+	images: Vec<GlyphImage<P>>,
+	format: Format,
+	queue: &Arc<Queue>,
+) -> Option<Atlas> {
+	if images.is_empty() {
+		return None;
+	}
+
+	let mut rects = GroupedRectsToPlace::<u32, ()>::new();
+	for (glyph, image, ..) in &images {
+		rects.push_rect(*glyph, None, RectToInsert::new(image.width() + PADDING * 2, image.height() + PADDING * 2, 1));
+	}
+
+	// the atlas starts small, and doubles in size until everything fits
+	let mut size = MIN_ATLAS_SIZE;
+	let placements = loop {
+		let mut bins = BTreeMap::new();
+		bins.insert((), TargetBin::new(size, size, 1));
+		match pack_rects(&rects, &mut bins, &volume_heuristic, &contains_smallest_box) {
+			Ok(placements) => break placements,
+			Err(_) if size < MAX_ATLAS_SIZE => size *= 2,
+			Err(_) => {
+				eprintln!("{} glyph images don't fit in a {}px atlas", images.len(), MAX_ATLAS_SIZE);
+				return None;
+			},
+		}
+	};
+
+	let mut pixels = ImageBuffer::<P, Vec<u8>>::new(size, size);
+	let mut bitmaps = vec![];
+	for (glyph, image, offset, ppem) in images {
+		let (_, location) = &placements.packed_locations()[&glyph];
+		let position = Vector2::new(location.x() + PADDING, location.y() + PADDING);
+		pixels.copy_from(&image, position.x, position.y).unwrap();
+
+		let image_size = Vector2::new(image.width() as f32, image.height() as f32);
+		bitmaps.push((glyph, BitmapGlyph {
+			page: 0,
+			offset,
+			size: image_size,
+			uv: Vector2::new(position.x as f32, position.y as f32) / size as f32,
+			uv_size: image_size / size as f32,
+			ppem,
+		}));
+	}
+
+	let dimensions = ImageDimensions::Dim2d { width: size, height: size, array_layers: 1 };
+	let (image, future) =
+		ImmutableImage::from_iter(pixels.into_raw().into_iter(), dimensions, MipmapsCount::One, format, queue.clone())
+			.unwrap();
+
+	Some(Atlas { image: ImageView::new(image).unwrap(), glyphs: bitmaps, future: Box::new(future) })
+}
@@ -0,0 +1,465 @@
+use crate::gfx::{
+	render::TriangleVertex,
+	text::{atlas::GlyphImage, GlyphInfo2, GlyphPage},
+};
+use image::{GrayImage, Luma};
+use nalgebra::Vector2;
+use std::{
+	convert::TryInto,
+	fs, io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+use vulkano::{
+	buffer::{BufferUsage, ImmutableBuffer},
+	device::Queue,
+	sync::GpuFuture,
+};
+
+// bump this whenever TriangleBuilder's output, the distance fields or the file layout change, so old files are rebuilt
+// instead of loaded
+const VERSION: u32 = 3;
+// each font gets one file, holding every batch of glyphs loaded from it. past this many, the oldest are dropped.
+const MAX_BATCHES: usize = 64;
+// past this size, the least recently written files in the cache directory are deleted
+const MAX_DIR_SIZE: u64 = 64 * 1024 * 1024;
+
+// something built from a font's glyphs that's slow enough to be worth saving. each kind gets its own file per font.
+pub(super) trait Cached: Sized {
+	// tells the kinds of files apart
+	const MAGIC: &'static [u8; 4];
+
+	fn encode(&self) -> Vec<u8>;
+	fn decode(data: &[u8]) -> Option<Self>;
+
+	// returns None if the file is missing, truncated, was written for a different version, kind or font, or doesn't
+	// have this glyph set
+	fn read(path: &Path, key: CacheKey) -> Option<Self> {
+		let data = fs::read(path).ok()?;
+		let batches = read_batches(&data, Self::MAGIC, key.font)?;
+		let (_, bytes) = batches.into_iter().find(|&(glyphs, _)| glyphs == key.glyphs)?;
+		Self::decode(bytes)
+	}
+
+	// adds the glyphs to their font's file, replacing an older batch of the same glyphs
+	fn write(&self, path: &Path, key: CacheKey) -> io::Result<()> {
+		let old = fs::read(path).unwrap_or_default();
+		let mut batches = read_batches(&old, Self::MAGIC, key.font).unwrap_or_default();
+		batches.retain(|&(glyphs, _)| glyphs != key.glyphs);
+		let encoded = self.encode();
+		batches.push((key.glyphs, &encoded[..]));
+		let batches = &batches[batches.len().saturating_sub(MAX_BATCHES)..];
+
+		let mut data = vec![];
+		data.extend_from_slice(Self::MAGIC);
+		data.extend_from_slice(&VERSION.to_le_bytes());
+		data.extend_from_slice(&key.font.to_le_bytes());
+		data.extend_from_slice(&(batches.len() as u32).to_le_bytes());
+		for (glyphs, bytes) in batches {
+			data.extend_from_slice(&glyphs.to_le_bytes());
+			data.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+			data.extend_from_slice(bytes);
+		}
+
+		// other processes may be loading the same font, so they should never see a partially written file
+		let dir = path.parent().unwrap_or_else(|| Path::new("."));
+		fs::create_dir_all(dir)?;
+		let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+		fs::write(&tmp_path, data)?;
+		fs::rename(tmp_path, path)?;
+
+		prune(dir, MAX_DIR_SIZE)
+	}
+}
+
+// the tessellated outlines of a set of glyphs, before they're uploaded to a page
+#[derive(Debug, PartialEq)]
+pub(super) struct GlyphGeometry {
+	pub verts: Vec<TriangleVertex>,
+	pub indices: Vec<u16>,
+	pub qverts: Vec<TriangleVertex>,
+	pub glyphs: Vec<(u32, GlyphInfo2)>,
+}
+impl GlyphGeometry {
+	pub fn upload(mut self, queue: &Arc<Queue>) -> (GlyphPage, Box<dyn GpuFuture + Send + Sync>) {
+		// empty buffers can't be created, and a page may not have any triangles or curves at all
+		if self.indices.is_empty() {
+			self.indices.extend_from_slice(&[0, 0, 0]);
+		}
+		if self.qverts.is_empty() {
+			self.qverts.extend_from_slice(&[TriangleVertex::default(); 3]);
+		}
+
+		let (verts, verts_future) =
+			ImmutableBuffer::from_iter(self.verts.into_iter(), BufferUsage::vertex_buffer(), queue.clone()).unwrap();
+		let (indices, indices_future) =
+			ImmutableBuffer::from_iter(self.indices.into_iter(), BufferUsage::index_buffer(), queue.clone()).unwrap();
+		let (qverts, qverts_future) =
+			ImmutableBuffer::from_iter(self.qverts.into_iter(), BufferUsage::vertex_buffer(), queue.clone()).unwrap();
+
+		let page = GlyphPage { indices, verts, qverts };
+		(page, Box::new(verts_future.join(indices_future).join(qverts_future)))
+	}
+}
+impl Cached for GlyphGeometry {
+	const MAGIC: &'static [u8; 4] = b"GLYC";
+
+	fn decode(data: &[u8]) -> Option<Self> {
+		let mut reader = Reader(data);
+
+		let vert_count = reader.u32()? as usize;
+		let index_count = reader.u32()? as usize;
+		let qvert_count = reader.u32()? as usize;
+		let glyph_count = reader.u32()? as usize;
+
+		let verts = reader.verts(vert_count)?;
+		let indices = (0..index_count).map(|_| reader.u16()).collect::<Option<_>>()?;
+		let qverts = reader.verts(qvert_count)?;
+		let glyphs = (0..glyph_count)
+			.map(|_| {
+				let id = reader.u32()?;
+				let glyph = GlyphInfo2 {
+					page: 0,
+					index_count: reader.u32()?,
+					first_index: reader.u32()?,
+					vertex_offset: reader.u32()?,
+					qvertex_count: reader.u32()?,
+					qvertex_offset: reader.u32()?,
+				};
+				Some((id, glyph))
+			})
+			.collect::<Option<_>>()?;
+
+		if !reader.0.is_empty() {
+			return None;
+		}
+
+		Some(Self { verts, indices, qverts, glyphs })
+	}
+
+	fn encode(&self) -> Vec<u8> {
+		let mut data = vec![];
+		data.extend_from_slice(&(self.verts.len() as u32).to_le_bytes());
+		data.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+		data.extend_from_slice(&(self.qverts.len() as u32).to_le_bytes());
+		data.extend_from_slice(&(self.glyphs.len() as u32).to_le_bytes());
+
+		write_verts(&mut data, &self.verts);
+		for index in &self.indices {
+			data.extend_from_slice(&index.to_le_bytes());
+		}
+		write_verts(&mut data, &self.qverts);
+		for (id, glyph) in &self.glyphs {
+			let fields = [
+				*id,
+				glyph.index_count,
+				glyph.first_index,
+				glyph.vertex_offset,
+				glyph.qvertex_count,
+				glyph.qvertex_offset,
+			];
+			for field in &fields {
+				data.extend_from_slice(&field.to_le_bytes());
+			}
+		}
+		data
+	}
+}
+
+// the distance fields of a set of glyphs, before they're packed into an atlas. glyphs without an outline don't have
+// one.
+#[derive(Debug, PartialEq)]
+pub(super) struct DistanceFields {
+	pub fields: Vec<GlyphImage<Luma<u8>>>,
+}
+impl Cached for DistanceFields {
+	const MAGIC: &'static [u8; 4] = b"GLYS";
+
+	fn decode(data: &[u8]) -> Option<Self> {
+		let mut reader = Reader(data);
+		let count = reader.u32()?;
+		let fields = (0..count)
+			.map(|_| {
+				let (id, width, height) = (reader.u32()?, reader.u32()?, reader.u32()?);
+				let offset = Vector2::new(reader.f32()?, reader.f32()?);
+				let ppem = reader.f32()?;
+				let len = (width as usize).checked_mul(height as usize)?;
+				let image = GrayImage::from_raw(width, height, reader.bytes(len)?.to_vec())?;
+				Some((id, image, offset, ppem))
+			})
+			.collect::<Option<_>>()?;
+
+		if !reader.0.is_empty() {
+			return None;
+		}
+
+		Some(Self { fields })
+	}
+
+	fn encode(&self) -> Vec<u8> {
+		let mut data = vec![];
+		data.extend_from_slice(&(self.fields.len() as u32).to_le_bytes());
+		for (id, image, offset, ppem) in &self.fields {
+			for field in &[*id, image.width(), image.height()] {
+				data.extend_from_slice(&field.to_le_bytes());
+			}
+			for field in &[offset.x, offset.y, *ppem] {
+				data.extend_from_slice(&field.to_le_bytes());
+			}
+			data.extend_from_slice(image.as_raw());
+		}
+		data
+	}
+}
+
+// the glyph set hash and encoded data of each batch in a font's file, oldest first
+fn read_batches<'a>(data: &'a [u8], magic: &[u8; 4], font: u64) -> Option<Vec<(u64, &'a [u8])>> {
+	let mut reader = Reader(data);
+	if reader.bytes(4)? != magic || reader.u32()? != VERSION || reader.u64()? != font {
+		return None;
+	}
+
+	let count = reader.u32()?;
+	let batches = (0..count)
+		.map(|_| {
+			let glyphs = reader.u64()?;
+			let len = reader.u64()?;
+			Some((glyphs, reader.bytes(len.try_into().ok()?)?))
+		})
+		.collect::<Option<_>>()?;
+
+	if !reader.0.is_empty() {
+		return None;
+	}
+	Some(batches)
+}
+
+// deletes the least recently written files until the directory is under max_size
+fn prune(dir: &Path, max_size: u64) -> io::Result<()> {
+	let mut files = vec![];
+	for entry in fs::read_dir(dir)? {
+		let entry = entry?;
+		let metadata = entry.metadata()?;
+		if metadata.is_file() {
+			files.push((metadata.modified()?, metadata.len(), entry.path()));
+		}
+	}
+
+	let mut size: u64 = files.iter().map(|&(_, len, _)| len).sum();
+	files.sort_unstable();
+	for (_, len, path) in files {
+		if size <= max_size {
+			break;
+		}
+		fs::remove_file(path)?;
+		size -= len;
+	}
+	Ok(())
+}
+
+// identifies a font's data and settings, and the set of glyphs that were loaded from it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) struct CacheKey {
+	pub font: u64,
+	pub glyphs: u64,
+}
+impl CacheKey {
+	pub fn new(font: u64, glyphs: &[u32]) -> Self {
+		let mut sorted = glyphs.to_vec();
+		sorted.sort_unstable();
+		let bytes: Vec<u8> = sorted.iter().flat_map(|glyph| glyph.to_le_bytes()).collect();
+		Self { font, glyphs: hash(&bytes) }
+	}
+
+	pub fn path(&self, dir: &Path) -> PathBuf {
+		dir.join(format!("{:016x}.bin", self.font))
+	}
+}
+
+// FNV-1a, since the std hashers aren't guaranteed to give the same results across builds
+pub(super) fn hash(data: &[u8]) -> u64 {
+	data.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn write_verts(data: &mut Vec<u8>, verts: &[TriangleVertex]) {
+	for vert in verts {
+		data.extend_from_slice(&vert.v_pos[0].to_le_bytes());
+		data.extend_from_slice(&vert.v_pos[1].to_le_bytes());
+	}
+}
+
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+		if self.0.len() < len {
+			return None;
+		}
+		let (bytes, rest) = self.0.split_at(len);
+		self.0 = rest;
+		Some(bytes)
+	}
+
+	fn u16(&mut self) -> Option<u16> {
+		Some(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+	}
+
+	fn u32(&mut self) -> Option<u32> {
+		Some(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+	}
+
+	fn u64(&mut self) -> Option<u64> {
+		Some(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+	}
+
+	fn f32(&mut self) -> Option<f32> {
+		Some(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+	}
+
+	fn verts(&mut self, count: usize) -> Option<Vec<TriangleVertex>> {
+		(0..count).map(|_| Some(TriangleVertex { v_pos: [self.f32()?, self.f32()?] })).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gfx::text;
+	use std::env;
+
+	fn geometry(offset: f32) -> GlyphGeometry {
+		let vert = |x: f32, y: f32| TriangleVertex { v_pos: [x + offset, y] };
+		let glyph = |index_count, first_index| GlyphInfo2 {
+			page: 0,
+			index_count,
+			first_index,
+			vertex_offset: 2,
+			qvertex_count: 3,
+			qvertex_offset: 0,
+		};
+		GlyphGeometry {
+			verts: vec![vert(0.0, 0.0), vert(0.0, 0.0), vert(1.0, -2.5), vert(3.0, 4.0)],
+			indices: vec![0, 1, 2, 0, 2, 3],
+			qverts: vec![vert(0.5, 0.5), vert(1.0, 1.0), vert(2.0, 0.0)],
+			glyphs: vec![(36, glyph(3, 0)), (37, glyph(3, 3))],
+		}
+	}
+
+	// each test gets its own directory, since they run in parallel
+	fn test_dir(name: &str) -> PathBuf {
+		let dir = env::temp_dir().join(format!("glyph-cache-test-{}-{}", std::process::id(), name));
+		fs::remove_dir_all(&dir).ok();
+		dir
+	}
+
+	#[test]
+	fn round_trip() {
+		let dir = test_dir("round-trip");
+		let key = CacheKey::new(1, &[37, 36]);
+		let path = key.path(&dir);
+		geometry(0.0).write(&path, key).unwrap();
+		assert_eq!(GlyphGeometry::read(&path, key), Some(geometry(0.0)));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn tessellated_round_trip() {
+		let dir = test_dir("tessellated");
+		let data = fs::read("res/Roboto-Regular.ttf").unwrap();
+		let face = ttf_parser::Face::from_slice(&data, 0).unwrap();
+		// includes a space, which has no outline, and glyphs with curves and holes
+		let glyphs: Vec<_> = "Hello, world! g@8".chars().map(|ch| face.glyph_index(ch).unwrap().0 as u32).collect();
+		let geometry = text::tessellate(&data, 0, &[], None, &glyphs).unwrap();
+		assert_eq!(geometry.glyphs.len(), glyphs.len());
+
+		let key = CacheKey::new(1, &glyphs);
+		let path = key.path(&dir);
+		geometry.write(&path, key).unwrap();
+		assert_eq!(GlyphGeometry::read(&path, key), Some(geometry));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn distance_fields_round_trip() {
+		let dir = test_dir("distance-fields");
+		let key = CacheKey::new(1, &[36, 37]);
+		let path = key.path(&dir);
+		let image = GrayImage::from_fn(3, 2, |x, y| Luma([(x * 10 + y) as u8]));
+		let fields = DistanceFields { fields: vec![(36, image, Vector2::new(-1.0, -4.5), 32.0)] };
+		fields.write(&path, key).unwrap();
+		assert_eq!(DistanceFields::read(&path, key), Some(fields));
+		// the kinds of files are told apart, even for the same font and glyphs
+		assert_eq!(GlyphGeometry::read(&path, key), None);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn batches_share_a_file() {
+		let dir = test_dir("batches");
+		let first = CacheKey::new(1, &[36, 37]);
+		let second = CacheKey::new(1, &[38]);
+		assert_eq!(first.path(&dir), second.path(&dir));
+
+		let path = first.path(&dir);
+		geometry(0.0).write(&path, first).unwrap();
+		geometry(1.0).write(&path, second).unwrap();
+		assert_eq!(GlyphGeometry::read(&path, first), Some(geometry(0.0)));
+		assert_eq!(GlyphGeometry::read(&path, second), Some(geometry(1.0)));
+		assert_eq!(GlyphGeometry::read(&path, CacheKey::new(1, &[39])), None);
+		assert_eq!(GlyphGeometry::read(&path, CacheKey::new(2, &[36, 37])), None);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn oldest_batches_are_dropped() {
+		let dir = test_dir("dropped");
+		let key = |i: u32| CacheKey::new(1, &[i]);
+		let path = key(0).path(&dir);
+		for i in 0..=MAX_BATCHES as u32 {
+			geometry(0.0).write(&path, key(i)).unwrap();
+		}
+		assert_eq!(GlyphGeometry::read(&path, key(0)), None);
+		assert_eq!(GlyphGeometry::read(&path, key(1)), Some(geometry(0.0)));
+		assert_eq!(GlyphGeometry::read(&path, key(MAX_BATCHES as u32)), Some(geometry(0.0)));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn bad_header() {
+		let dir = test_dir("bad-header");
+		let key = CacheKey::new(1, &[36, 37]);
+		let path = key.path(&dir);
+		geometry(0.0).write(&path, key).unwrap();
+		let data = fs::read(&path).unwrap();
+
+		let mut bad_magic = data.clone();
+		bad_magic[0] = b'X';
+		fs::write(&path, bad_magic).unwrap();
+		assert_eq!(GlyphGeometry::read(&path, key), None);
+
+		let mut bad_version = data.clone();
+		bad_version[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+		fs::write(&path, bad_version).unwrap();
+		assert_eq!(GlyphGeometry::read(&path, key), None);
+
+		fs::write(&path, &data[..data.len() - 1]).unwrap();
+		assert_eq!(GlyphGeometry::read(&path, key), None);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn prune_deletes_oldest() {
+		let dir = test_dir("prune");
+		fs::create_dir_all(&dir).unwrap();
+		let old = dir.join("old.bin");
+		let new = dir.join("new.bin");
+		fs::write(&old, [0; 100]).unwrap();
+		// some filesystems only keep modified times to the second
+		std::thread::sleep(std::time::Duration::from_millis(1100));
+		fs::write(&new, [0; 100]).unwrap();
+
+		prune(&dir, 150).unwrap();
+		assert!(!old.exists());
+		assert!(new.exists());
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
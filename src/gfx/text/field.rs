@@ -0,0 +1,368 @@
+use crate::gfx::text::{
+	layout::{Layout, LayoutOptions},
+	rich::{RichText, TextStyle},
+	FontId, Fonts,
+};
+use nalgebra::Vector2;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+
+// each paragraph is laid out on its own, so an edit only reshapes the paragraphs it touches. winit doesn't report
+// IME composition, but composed characters still arrive as ReceivedCharacter events.
+pub struct TextField {
+	// in pixels, from the top left corner of the window to the top left corner of the field
+	pub position: Vector2<f32>,
+	pub caret_color: [f32; 4],
+	pub selection_color: [f32; 4],
+	pub focused: bool,
+	font: FontId,
+	size: f32,
+	// part of each paragraph's style, so changing it lays the paragraphs out again
+	color: [f32; 4],
+	multiline: bool,
+	options: LayoutOptions,
+	text: String,
+	cursor: usize,
+	anchor: usize,
+	modifiers: ModifiersState,
+	undo: Vec<Edit>,
+	redo: Vec<Edit>,
+	paragraphs: Vec<Paragraph>,
+}
+impl TextField {
+	pub fn new(fonts: &Fonts, text: impl Into<String>, font: FontId, size: f32, multiline: bool) -> Self {
+		let mut text = text.into();
+		if !multiline {
+			text.retain(|ch| ch != '\n');
+		}
+		let cursor = text.len();
+
+		let mut field = Self {
+			position: Vector2::zeros(),
+			color: [1.0, 1.0, 1.0, 1.0],
+			caret_color: [1.0, 1.0, 1.0, 1.0],
+			selection_color: [0.2, 0.4, 0.8, 1.0],
+			focused: false,
+			font,
+			size,
+			multiline,
+			options: LayoutOptions::default(),
+			text,
+			cursor,
+			anchor: cursor,
+			modifiers: ModifiersState::empty(),
+			undo: vec![],
+			redo: vec![],
+			paragraphs: vec![],
+		};
+		field.paragraphs = field.layout_paragraphs(fonts, 0..field.text.len());
+		field.update_tops();
+		field
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	pub fn selection(&self) -> Range<usize> {
+		self.cursor.min(self.anchor)..self.cursor.max(self.anchor)
+	}
+
+	pub fn color(&self) -> [f32; 4] {
+		self.color
+	}
+
+	pub fn set_color(&mut self, fonts: &Fonts, color: [f32; 4]) {
+		self.color = color;
+		self.paragraphs = self.layout_paragraphs(fonts, 0..self.text.len());
+	}
+
+	pub fn set_options(&mut self, fonts: &Fonts, options: LayoutOptions) {
+		self.options = options;
+		self.paragraphs = self.layout_paragraphs(fonts, 0..self.text.len());
+		self.update_tops();
+	}
+
+	pub fn handle_event(&mut self, fonts: &Fonts, event: &WindowEvent) {
+		match event {
+			WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+			_ if !self.focused => (),
+			WindowEvent::ReceivedCharacter(ch) if !ch.is_control() => {
+				self.replace(fonts, self.selection(), &ch.to_string(), true);
+			},
+			WindowEvent::KeyboardInput {
+				input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. },
+				..
+			} => self.key(fonts, *key),
+			_ => (),
+		}
+	}
+
+	// each paragraph's layout, with where its origin goes in pixels
+	pub fn layouts(&self) -> impl Iterator<Item = (Vector2<f32>, &Layout)> {
+		self.paragraphs.iter().map(move |paragraph| (self.origin(paragraph), &paragraph.layout))
+	}
+
+	// the corners of the caret, in pixels
+	pub fn caret_rect(&self) -> (Vector2<f32>, Vector2<f32>) {
+		let paragraph = &self.paragraphs[self.paragraph_at(self.cursor)];
+		let caret = paragraph.layout.caret(self.cursor - paragraph.text.start);
+		let line = &paragraph.layout.lines[caret.line];
+		let origin = self.origin(paragraph);
+		let width = (self.size / 16.0).max(1.0);
+		(
+			origin + Vector2::new(caret.offset - width / 2.0, line.baseline - line.ascent),
+			origin + Vector2::new(caret.offset + width / 2.0, line.baseline - line.descent),
+		)
+	}
+
+	// the corners of each rectangle covering the selection, in pixels
+	pub fn selection_rects(&self) -> Vec<(Vector2<f32>, Vector2<f32>)> {
+		let selection = self.selection();
+		if selection.is_empty() {
+			return vec![];
+		}
+
+		let mut rects = vec![];
+		for paragraph in &self.paragraphs {
+			if paragraph.text.end < selection.start || paragraph.text.start > selection.end {
+				continue;
+			}
+			let start = selection.start.saturating_sub(paragraph.text.start);
+			let end = selection.end.min(paragraph.text.end) - paragraph.text.start;
+			let origin = self.origin(paragraph);
+			rects.extend(
+				paragraph.layout.selection(start..end).into_iter().map(|(min, max)| (origin + min, origin + max)),
+			);
+		}
+		rects
+	}
+
+	fn key(&mut self, fonts: &Fonts, key: VirtualKeyCode) {
+		let shift = self.modifiers.shift();
+		let ctrl = self.modifiers.ctrl();
+		let selection = self.selection();
+
+		match key {
+			VirtualKeyCode::Left if !shift && !selection.is_empty() => self.move_to(selection.start, false),
+			VirtualKeyCode::Right if !shift && !selection.is_empty() => self.move_to(selection.end, false),
+			VirtualKeyCode::Left if ctrl => self.move_to(self.prev_word(self.cursor), shift),
+			VirtualKeyCode::Right if ctrl => self.move_to(self.next_word(self.cursor), shift),
+			VirtualKeyCode::Left => self.move_to(self.prev_grapheme(self.cursor), shift),
+			VirtualKeyCode::Right => self.move_to(self.next_grapheme(self.cursor), shift),
+			VirtualKeyCode::Up => self.move_to(self.vertical(false), shift),
+			VirtualKeyCode::Down => self.move_to(self.vertical(true), shift),
+			VirtualKeyCode::Home if ctrl => self.move_to(0, shift),
+			VirtualKeyCode::End if ctrl => self.move_to(self.text.len(), shift),
+			VirtualKeyCode::Home => self.move_to(self.line_range().start, shift),
+			VirtualKeyCode::End => self.move_to(self.line_range().end, shift),
+			VirtualKeyCode::Back | VirtualKeyCode::Delete if !selection.is_empty() => {
+				self.replace(fonts, selection, "", false)
+			},
+			VirtualKeyCode::Back => {
+				let start = if ctrl { self.prev_word(self.cursor) } else { self.prev_grapheme(self.cursor) };
+				self.replace(fonts, start..self.cursor, "", false);
+			},
+			VirtualKeyCode::Delete => {
+				let end = if ctrl { self.next_word(self.cursor) } else { self.next_grapheme(self.cursor) };
+				self.replace(fonts, self.cursor..end, "", false);
+			},
+			VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter if self.multiline => {
+				self.replace(fonts, selection, "\n", false)
+			},
+			VirtualKeyCode::A if ctrl => {
+				self.anchor = 0;
+				self.cursor = self.text.len();
+			},
+			VirtualKeyCode::Z if ctrl && shift => self.redo(fonts),
+			VirtualKeyCode::Z if ctrl => self.undo(fonts),
+			VirtualKeyCode::Y if ctrl => self.redo(fonts),
+			_ => (),
+		}
+	}
+
+	fn move_to(&mut self, index: usize, extend: bool) {
+		self.cursor = index;
+		if !extend {
+			self.anchor = index;
+		}
+	}
+
+	fn replace(&mut self, fonts: &Fonts, range: Range<usize>, inserted: &str, typing: bool) {
+		if range.is_empty() && inserted.is_empty() {
+			return;
+		}
+
+		self.redo.clear();
+		match self.undo.last_mut() {
+			// consecutive typing is undone all at once
+			Some(last)
+				if typing && last.typing && range.is_empty() && last.start + last.inserted.len() == range.start =>
+			{
+				last.inserted.push_str(inserted)
+			},
+			_ => self.undo.push(Edit {
+				start: range.start,
+				removed: self.text[range.clone()].to_owned(),
+				inserted: inserted.to_owned(),
+				cursor: self.cursor,
+				anchor: self.anchor,
+				typing,
+			}),
+		}
+
+		self.splice(fonts, range.clone(), inserted);
+		self.move_to(range.start + inserted.len(), false);
+	}
+
+	fn undo(&mut self, fonts: &Fonts) {
+		if let Some(edit) = self.undo.pop() {
+			self.splice(fonts, edit.start..edit.start + edit.inserted.len(), &edit.removed);
+			self.cursor = edit.cursor;
+			self.anchor = edit.anchor;
+			self.redo.push(edit);
+		}
+	}
+
+	fn redo(&mut self, fonts: &Fonts) {
+		if let Some(edit) = self.redo.pop() {
+			self.splice(fonts, edit.start..edit.start + edit.removed.len(), &edit.inserted);
+			self.move_to(edit.start + edit.inserted.len(), false);
+			self.undo.push(Edit { typing: false, ..edit });
+		}
+	}
+
+	fn splice(&mut self, fonts: &Fonts, range: Range<usize>, inserted: &str) {
+		let first = self.paragraph_at(range.start);
+		let last = self.paragraph_at(range.end);
+		let start = self.paragraphs[first].text.start;
+		let end = self.paragraphs[last].text.end - range.len() + inserted.len();
+
+		self.text.replace_range(range.clone(), inserted);
+		for paragraph in &mut self.paragraphs[last + 1..] {
+			paragraph.text =
+				paragraph.text.start - range.len() + inserted.len()..paragraph.text.end - range.len() + inserted.len();
+		}
+		let paragraphs = self.layout_paragraphs(fonts, start..end);
+		self.paragraphs.splice(first..=last, paragraphs);
+		self.update_tops();
+	}
+
+	fn layout_paragraphs(&self, fonts: &Fonts, range: Range<usize>) -> Vec<Paragraph> {
+		let style = TextStyle::new(self.font, self.size).color(self.color);
+		let mut paragraphs = vec![];
+		let mut start = range.start;
+		for end in self.text[range.clone()].match_indices('\n').map(|(i, _)| range.start + i).chain(Some(range.end)) {
+			let text = RichText::plain(&self.text[start..end], style.clone());
+			let layout = Layout::new(fonts, &text, &self.options);
+			paragraphs.push(Paragraph { text: start..end, layout, top: 0.0 });
+			start = end + 1;
+		}
+		paragraphs
+	}
+
+	// paragraphs are spaced like the lines within them, the same way Layout::new steps from one line to the next
+	fn update_tops(&mut self) {
+		// the baseline, descent and line gap of the previous paragraph's last line
+		let mut previous: Option<(f32, f32, f32)> = None;
+		for paragraph in &mut self.paragraphs {
+			let (first, last) = (&paragraph.layout.lines[0], paragraph.layout.lines.last().unwrap());
+			let baseline = match previous {
+				Some((baseline, descent, line_gap)) => {
+					baseline + (first.ascent - descent + line_gap) * self.options.line_height
+				},
+				None => first.ascent,
+			};
+			paragraph.top = baseline - first.ascent;
+			previous = Some((baseline + last.baseline, last.descent, last.line_gap));
+		}
+	}
+
+	fn origin(&self, paragraph: &Paragraph) -> Vector2<f32> {
+		self.position + Vector2::new(0.0, paragraph.top + paragraph.layout.lines[0].ascent)
+	}
+
+	fn paragraph_at(&self, index: usize) -> usize {
+		self.paragraphs.iter().rposition(|paragraph| paragraph.text.start <= index).unwrap_or(0)
+	}
+
+	// the text of the line the cursor is on. a wrapped line ends before its trailing whitespace, so the caret stays on
+	// it instead of moving to the start of the next line.
+	fn line_range(&self) -> Range<usize> {
+		let paragraph = &self.paragraphs[self.paragraph_at(self.cursor)];
+		let layout = &paragraph.layout;
+		let line = layout.caret(self.cursor - paragraph.text.start).line;
+		let text = &layout.lines[line].text;
+		let end = if line + 1 < layout.lines.len() {
+			text.start + layout.text[text.clone()].trim_end().len()
+		} else {
+			text.end
+		};
+		paragraph.text.start + text.start..paragraph.text.start + end
+	}
+
+	// the caret position on the line above or below the cursor that's closest to it
+	fn vertical(&self, down: bool) -> usize {
+		let p = self.paragraph_at(self.cursor);
+		let layout = &self.paragraphs[p].layout;
+		let caret = layout.caret(self.cursor - self.paragraphs[p].text.start);
+
+		let target = if down && caret.line + 1 < layout.lines.len() {
+			(p, caret.line + 1)
+		} else if down && p + 1 < self.paragraphs.len() {
+			(p + 1, 0)
+		} else if down {
+			return self.text.len();
+		} else if caret.line > 0 {
+			(p, caret.line - 1)
+		} else if p > 0 {
+			(p - 1, self.paragraphs[p - 1].layout.lines.len() - 1)
+		} else {
+			return 0;
+		};
+
+		let paragraph = &self.paragraphs[target.0];
+		let baseline = paragraph.layout.lines[target.1].baseline;
+		paragraph.text.start + paragraph.layout.hit(Vector2::new(caret.offset, baseline))
+	}
+
+	fn next_grapheme(&self, index: usize) -> usize {
+		self.text[index..].graphemes(true).next().map_or(index, |grapheme| index + grapheme.len())
+	}
+
+	fn prev_grapheme(&self, index: usize) -> usize {
+		self.text[..index].graphemes(true).next_back().map_or(index, |grapheme| index - grapheme.len())
+	}
+
+	// the end of the next word
+	fn next_word(&self, index: usize) -> usize {
+		let mut words = self.text[index..].split_word_bound_indices();
+		let word = words.find(|(_, word)| !word.trim().is_empty());
+		word.map_or(self.text.len(), |(i, word)| index + i + word.len())
+	}
+
+	// the start of the previous word
+	fn prev_word(&self, index: usize) -> usize {
+		let mut words = self.text[..index].split_word_bound_indices().rev();
+		words.find(|(_, word)| !word.trim().is_empty()).map_or(0, |(i, _)| i)
+	}
+}
+
+struct Paragraph {
+	// excludes the newline
+	text: Range<usize>,
+	layout: Layout,
+	// the distance from the top of the field to the top of the paragraph's first line
+	top: f32,
+}
+
+struct Edit {
+	start: usize,
+	removed: String,
+	inserted: String,
+	// the selection before the edit
+	cursor: usize,
+	anchor: usize,
+	typing: bool,
+}
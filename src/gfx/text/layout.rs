@@ -0,0 +1,802 @@
+use crate::gfx::text::{
+	rich::{RichText, TextStyle},
+	Fonts,
+};
+use harfbuzz_rs::{shape, Direction as HDirection, Feature as HFeature, Language, Tag, UnicodeBuffer};
+use harfbuzz_sys as hb;
+use nalgebra::Vector2;
+use std::{
+	collections::BTreeMap,
+	ops::{Bound, Range, RangeBounds},
+};
+use unicode_bidi::BidiInfo;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+	Left,
+	Center,
+	Right,
+	Justify,
+}
+
+// vertical lines are laid out from top to bottom, and stack from right to left
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+	Horizontal,
+	Vertical,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutOptions {
+	pub direction: Direction,
+	// in pixels; horizontal lines only wrap when max_width is set, and vertical lines when max_height is
+	pub max_width: Option<f32>,
+	pub max_height: Option<f32>,
+	// for vertical text, left and right mean top and bottom
+	pub align: Align,
+	// a multiple of the line height the font asks for, or of the em size for vertical lines
+	pub line_height: f32,
+	pub features: Vec<Feature>,
+}
+impl Default for LayoutOptions {
+	fn default() -> Self {
+		Self {
+			direction: Direction::Horizontal,
+			max_width: None,
+			max_height: None,
+			align: Align::Left,
+			line_height: 1.0,
+			features: vec![],
+		}
+	}
+}
+
+// an opentype feature, like kern, liga, tnum, smcp or ss01, applied to a byte range of the text
+#[derive(Debug, Clone)]
+pub struct Feature {
+	pub tag: Tag,
+	pub value: u32,
+	pub range: Range<usize>,
+}
+impl Feature {
+	pub fn new(tag: &[u8; 4], value: u32, range: impl RangeBounds<usize>) -> Self {
+		let start = match range.start_bound() {
+			Bound::Included(&i) => i,
+			Bound::Excluded(&i) => i + 1,
+			Bound::Unbounded => 0,
+		};
+		let end = match range.end_bound() {
+			Bound::Included(&i) => i + 1,
+			Bound::Excluded(&i) => i,
+			Bound::Unbounded => usize::MAX,
+		};
+		Self { tag: Tag::new(tag[0] as char, tag[1] as char, tag[2] as char, tag[3] as char), value, range: start..end }
+	}
+}
+
+// positions are in pixels, with y pointing down. horizontal text puts the first line's baseline at y = 0, and
+// vertical text puts the top of the first line at y = 0 and its center at x = 0.
+#[derive(Debug, Clone)]
+pub struct Layout {
+	pub text: String,
+	pub direction: Direction,
+	// the byte range of the text each span covers
+	pub spans: Vec<(Range<usize>, TextStyle)>,
+	pub glyphs: Vec<LayoutGlyph>,
+	pub lines: Vec<Line>,
+}
+impl Layout {
+	pub fn new(fonts: &Fonts, rich_text: &RichText, options: &LayoutOptions) -> Self {
+		let mut text = String::new();
+		let mut spans = vec![];
+		for span in &rich_text.spans {
+			let start = text.len();
+			text.push_str(&span.text);
+			spans.push((start..text.len(), span.style.clone()));
+		}
+		if spans.is_empty() {
+			return Self { text, direction: options.direction, spans, glyphs: vec![], lines: vec![] };
+		}
+
+		let vertical = options.direction == Direction::Vertical;
+		let max_length = if vertical { options.max_height } else { options.max_width };
+
+		let mut paragraphs = vec![];
+		let mut allowed = vec![];
+		let mut start = 0;
+		for (i, opportunity) in linebreaks(&text) {
+			match opportunity {
+				BreakOpportunity::Allowed => allowed.push(i),
+				BreakOpportunity::Mandatory if i > start => {
+					paragraphs.push((start..i, allowed.split_off(0)));
+					start = i;
+				},
+				BreakOpportunity::Mandatory => (),
+			}
+		}
+		// an empty string, or one that ends in a newline, still has a line at the end to put a caret on
+		if text.is_empty() || text.ends_with(is_line_terminator) {
+			paragraphs.push((text.len()..text.len(), vec![]));
+		}
+
+		let paragraph = Paragraph { fonts, text: &text, spans: &spans, options, max_length };
+		let mut shaped = vec![];
+		let mut lines: Vec<Line> = vec![];
+		for (range, allowed) in paragraphs {
+			let (paragraph_glyphs, mut paragraph_lines) = paragraph.layout(range, &allowed);
+			// the last line of a paragraph is never stretched
+			paragraph_lines.last_mut().unwrap().justify = false;
+			for mut line in paragraph_lines {
+				line.glyphs = shaped.len() + line.glyphs.start..shaped.len() + line.glyphs.end;
+				if let Some(previous) = lines.last() {
+					let step = (line.ascent - previous.descent + previous.line_gap) * options.line_height;
+					// vertical lines stack to the left
+					line.baseline = if vertical { previous.baseline - step } else { previous.baseline + step };
+				}
+				lines.push(line);
+			}
+			shaped.extend(paragraph_glyphs);
+		}
+
+		let align_length = max_length.unwrap_or_else(|| lines.iter().map(|line| line.length).fold(0.0, f32::max));
+		let mut glyphs = Vec::with_capacity(shaped.len());
+		for line in &mut lines {
+			let line_glyphs = &shaped[line.glyphs.clone()];
+			let spaces = line_glyphs.iter().filter(|glyph| glyph.space && !glyph.trailing).count();
+			let free = align_length - line.length;
+			let (start, stretch) = match options.align {
+				Align::Left => (0.0, 0.0),
+				Align::Center => (free / 2.0, 0.0),
+				Align::Right => (free, 0.0),
+				Align::Justify if line.justify && spaces > 0 => (0.0, free / spaces as f32),
+				Align::Justify => (0.0, 0.0),
+			};
+			line.start = start;
+
+			// in a right-to-left paragraph, trailing whitespace ends up on the left, outside the aligned part
+			let lead = match line_glyphs.iter().position(|glyph| !glyph.trailing) {
+				Some(i) => line_glyphs[..i].iter().map(|glyph| glyph.advance.x).sum(),
+				None => 0.0,
+			};
+
+			let mut cursor =
+				if vertical { Vector2::new(line.baseline, start) } else { Vector2::new(start - lead, line.baseline) };
+			for glyph in line_glyphs {
+				glyphs.push(LayoutGlyph {
+					id: glyph.id,
+					cluster: glyph.cluster,
+					span: glyph.span,
+					position: cursor + glyph.offset,
+					advance: glyph.advance,
+					offset: glyph.offset,
+					rtl: glyph.rtl,
+				});
+
+				cursor += glyph.advance;
+				if glyph.space && !glyph.trailing {
+					if vertical {
+						cursor.y += stretch;
+					} else {
+						cursor.x += stretch;
+					}
+				}
+			}
+		}
+
+		Self { text, direction: options.direction, spans, glyphs, lines }
+	}
+
+	pub fn measure(&self, fonts: &Fonts) -> TextMetrics {
+		let mut lines = Vec::with_capacity(self.lines.len());
+		for line in &self.lines {
+			let mut ink: Option<(Vector2<f32>, Vector2<f32>)> = None;
+			for glyph in &self.glyphs[line.glyphs.clone()] {
+				let style = &self.spans[glyph.span].1;
+				let font = fonts.get(style.font);
+				if let Some((min, max)) = font.glyph_extents(glyph.id) {
+					let scale = style.size / font.metrics.units_per_em as f32;
+					let (min, max) = (glyph.position + min * scale, glyph.position + max * scale);
+					ink = Some(match ink {
+						Some((ink_min, ink_max)) => (ink_min.inf(&min), ink_max.sup(&max)),
+						None => (min, max),
+					});
+				}
+			}
+
+			lines.push(LineMetrics {
+				text: line.text.clone(),
+				start: line.start,
+				baseline: line.baseline,
+				advance: line.length,
+				ink,
+				ascent: line.ascent,
+				descent: line.descent,
+				line_gap: line.line_gap,
+			});
+		}
+
+		let ink = lines
+			.iter()
+			.filter_map(|line| line.ink)
+			.reduce(|(a_min, a_max), (b_min, b_max)| (a_min.inf(&b_min), a_max.sup(&b_max)));
+		TextMetrics {
+			advance: lines.iter().map(|line| line.advance).fold(0.0, f32::max),
+			ink,
+			ascent: lines.iter().map(|line| line.ascent).fold(0.0, f32::max),
+			descent: lines.iter().map(|line| line.descent).fold(0.0, f32::min),
+			line_gap: lines.iter().map(|line| line.line_gap).fold(0.0, f32::max),
+			lines,
+		}
+	}
+
+	// the lines under, through and over the spans that ask for them, at the positions and thicknesses their fonts
+	// give. trailing whitespace isn't decorated, and neither are vertical lines.
+	pub fn decorations(&self, fonts: &Fonts) -> Vec<Decoration> {
+		let mut decorations = vec![];
+		if self.direction == Direction::Vertical {
+			return decorations;
+		}
+
+		for line in &self.lines {
+			let content_end = line.text.start + self.text[line.text.clone()].trim_end().len();
+			let glyphs: Vec<_> =
+				self.glyphs[line.glyphs.clone()].iter().filter(|glyph| glyph.cluster < content_end).collect();
+
+			// runs of glyphs from the same span are decorated together, so their lines don't have seams
+			let mut start = 0;
+			while start < glyphs.len() {
+				let span = glyphs[start].span;
+				let end =
+					glyphs[start..].iter().position(|glyph| glyph.span != span).map_or(glyphs.len(), |i| start + i);
+				let (left, right) = glyphs[start..end].iter().fold((f32::MAX, f32::MIN), |(left, right), glyph| {
+					let pen = glyph.position.x - glyph.offset.x;
+					(left.min(pen), right.max(pen + glyph.advance.x))
+				});
+				start = end;
+
+				let style = &self.spans[span].1;
+				let font = fonts.get(style.font);
+				let scale = style.size / font.metrics.units_per_em as f32;
+				let metrics = &font.decoration_metrics;
+				let kinds = [
+					(style.underline, metrics.underline, style.skip_ink),
+					(style.strikethrough, metrics.strikethrough, false),
+					(style.overline, metrics.overline, false),
+				];
+				for &(enabled, (position, thickness), skip_ink) in &kinds {
+					if !enabled {
+						continue;
+					}
+
+					let top = line.baseline - position * scale;
+					let bottom = top + thickness * scale;
+					let mut pieces = vec![(left, right)];
+					if skip_ink {
+						// the gap is as wide as the line is thick
+						let gap = bottom - top;
+						for &glyph in &glyphs {
+							let style = &self.spans[glyph.span].1;
+							let font = fonts.get(style.font);
+							let (min, max) = match font.glyph_extents(glyph.id) {
+								Some(extents) => extents,
+								None => continue,
+							};
+							let scale = style.size / font.metrics.units_per_em as f32;
+							let (min, max) = (glyph.position + min * scale, glyph.position + max * scale);
+							if max.y > top - gap && min.y < bottom + gap {
+								pieces = cut(pieces, (min.x - gap, max.x + gap));
+							}
+						}
+					}
+
+					decorations.extend(pieces.into_iter().map(|(left, right)| Decoration {
+						span,
+						min: Vector2::new(left, top),
+						max: Vector2::new(right, bottom),
+					}));
+				}
+			}
+		}
+
+		decorations
+	}
+
+	pub fn grapheme_to_byte(&self, grapheme: usize) -> usize {
+		self.text.grapheme_indices(true).nth(grapheme).map_or(self.text.len(), |(i, _)| i)
+	}
+
+	// the line a caret at a byte index goes on. an index between two lines belongs to the second one.
+	pub fn line_at(&self, index: usize) -> usize {
+		self.lines.iter().rposition(|line| line.text.start <= index).unwrap_or(0)
+	}
+
+	// the caret before the grapheme at a byte index
+	pub fn caret(&self, index: usize) -> Caret {
+		let line = self.line_at(index);
+		if self.lines.is_empty() {
+			return Caret { line, offset: 0.0 };
+		}
+		let offset = self.caret_offset(line, &self.clusters(line), index);
+		Caret { line, offset }
+	}
+
+	// the byte index of the caret position nearest to a point
+	pub fn hit(&self, point: Vector2<f32>) -> usize {
+		let vertical = self.direction == Direction::Vertical;
+		let (along, across) = if vertical { (point.y, point.x) } else { (point.x, point.y) };
+
+		let distance = |line: &Line| {
+			let (low, high) = self.line_span(line);
+			(low - across).max(across - high).max(0.0)
+		};
+		let line = match (0..self.lines.len())
+			.min_by(|&a, &b| distance(&self.lines[a]).partial_cmp(&distance(&self.lines[b])).unwrap())
+		{
+			Some(line) => line,
+			None => return 0,
+		};
+
+		let clusters = self.clusters(line);
+		let text = self.lines[line].text.start..self.content_end(line);
+		let mut carets = self.graphemes(text.clone());
+		carets.push(text.end);
+		let distance = |&index: &usize| (self.caret_offset(line, &clusters, index) - along).abs();
+		carets.into_iter().min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap()).unwrap()
+	}
+
+	// the rectangles that cover a selected byte range, with the corners of each one
+	pub fn selection(&self, range: Range<usize>) -> Vec<(Vector2<f32>, Vector2<f32>)> {
+		let mut rects = vec![];
+		for (i, line) in self.lines.iter().enumerate() {
+			if line.text.end <= range.start || line.text.start >= range.end {
+				continue;
+			}
+
+			let mut spans = vec![];
+			for cluster in self.clusters(i) {
+				let graphemes = self.graphemes(cluster.text.clone());
+				let count = graphemes.len() as f32;
+				for (k, &start) in graphemes.iter().enumerate() {
+					let end = graphemes.get(k + 1).copied().unwrap_or(cluster.text.end);
+					if start < range.end && end > range.start {
+						let a = cluster.at(k as f32 / count);
+						let b = cluster.at((k + 1) as f32 / count);
+						spans.push((a.min(b), a.max(b)));
+					}
+				}
+			}
+			spans.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+			// bidi runs can split a selection into several pieces, but neighbouring clusters make one rectangle
+			let mut merged: Vec<(f32, f32)> = vec![];
+			for (start, end) in spans {
+				match merged.last_mut() {
+					Some(last) if start <= last.1 + 0.01 => last.1 = last.1.max(end),
+					_ => merged.push((start, end)),
+				}
+			}
+
+			let (low, high) = self.line_span(line);
+			for (start, end) in merged {
+				rects.push(match self.direction {
+					Direction::Horizontal => (Vector2::new(start, low), Vector2::new(end, high)),
+					Direction::Vertical => (Vector2::new(low, start), Vector2::new(high, end)),
+				});
+			}
+		}
+		rects
+	}
+
+	fn caret_offset(&self, line: usize, clusters: &[ClusterBox], index: usize) -> f32 {
+		if let Some(cluster) = clusters.iter().find(|cluster| cluster.text.contains(&index)) {
+			// a cluster with several graphemes, like a ligature, is split evenly between them
+			let graphemes = self.graphemes(cluster.text.clone());
+			let k = graphemes.iter().rposition(|&start| start <= index).unwrap_or(0);
+			return cluster.at(k as f32 / graphemes.len() as f32);
+		}
+
+		match clusters.iter().find(|cluster| cluster.text.end == index) {
+			Some(cluster) => cluster.at(1.0),
+			None => self.lines[line].start,
+		}
+	}
+
+	// the line's clusters in logical order
+	fn clusters(&self, line: usize) -> Vec<ClusterBox> {
+		let vertical = self.direction == Direction::Vertical;
+		let mut clusters = BTreeMap::new();
+		for glyph in &self.glyphs[self.lines[line].glyphs.clone()] {
+			let pen = glyph.position - glyph.offset;
+			let (start, advance) = if vertical { (pen.y, glyph.advance.y) } else { (pen.x, glyph.advance.x) };
+			let cluster = clusters.entry(glyph.cluster).or_insert(ClusterBox {
+				text: glyph.cluster..glyph.cluster,
+				start,
+				end: start,
+				rtl: glyph.rtl,
+			});
+			cluster.start = cluster.start.min(start);
+			cluster.end = cluster.end.max(start + advance);
+		}
+
+		let mut clusters: Vec<_> = clusters.into_iter().map(|(_, cluster)| cluster).collect();
+		let end = self.content_end(line);
+		for i in 0..clusters.len() {
+			clusters[i].text.end = clusters.get(i + 1).map_or(end, |next| next.text.start);
+		}
+		clusters
+	}
+
+	// the end of a line's text, without its line terminator
+	fn content_end(&self, line: usize) -> usize {
+		let text = &self.lines[line].text;
+		text.start + self.text[text.clone()].trim_end_matches(is_line_terminator).len()
+	}
+
+	fn graphemes(&self, range: Range<usize>) -> Vec<usize> {
+		self.text[range.clone()].grapheme_indices(true).map(|(i, _)| range.start + i).collect()
+	}
+
+	// the lowest and highest coordinates a line covers across its direction
+	fn line_span(&self, line: &Line) -> (f32, f32) {
+		match self.direction {
+			Direction::Horizontal => (line.baseline - line.ascent, line.baseline - line.descent),
+			Direction::Vertical => (line.baseline + line.descent, line.baseline + line.ascent),
+		}
+	}
+
+	// the corners of the box the lines fill, which can be smaller than the ink or hang outside of it
+	pub fn bounds(&self) -> (Vector2<f32>, Vector2<f32>) {
+		let (first, last) = match (self.lines.first(), self.lines.last()) {
+			(Some(first), Some(last)) => (first, last),
+			_ => return (Vector2::zeros(), Vector2::zeros()),
+		};
+		let start = self.lines.iter().map(|line| line.start).fold(f32::INFINITY, f32::min);
+		let end = self.lines.iter().map(|line| line.start + line.length).fold(f32::NEG_INFINITY, f32::max);
+
+		if self.direction == Direction::Vertical {
+			(Vector2::new(last.baseline + last.descent, start), Vector2::new(first.baseline + first.ascent, end))
+		} else {
+			(Vector2::new(start, first.baseline - first.ascent), Vector2::new(end, last.baseline - last.descent))
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LayoutGlyph {
+	pub id: u32,
+	// the byte index in the text of the first character this glyph was shaped from
+	pub cluster: usize,
+	// an index into the layout's spans
+	pub span: usize,
+	pub position: Vector2<f32>,
+	pub advance: Vector2<f32>,
+	// from the pen position to where the glyph is drawn
+	pub offset: Vector2<f32>,
+	// whether the glyph belongs to a right-to-left run, so its cluster starts on its right edge
+	pub rtl: bool,
+}
+
+// an underline, strikethrough or overline, with its corners
+#[derive(Debug, Copy, Clone)]
+pub struct Decoration {
+	// an index into the layout's spans
+	pub span: usize,
+	pub min: Vector2<f32>,
+	pub max: Vector2<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+	pub text: Range<usize>,
+	pub glyphs: Range<usize>,
+	// where the line begins along the direction it's written in
+	pub start: f32,
+	// where the line sits across that direction; an x coordinate for vertical lines
+	pub baseline: f32,
+	// excludes trailing whitespace
+	pub length: f32,
+	// the largest ascent, descent and line gap of the spans on the line, with the descent negative. vertical lines
+	// extend half an em to either side.
+	pub ascent: f32,
+	pub descent: f32,
+	pub line_gap: f32,
+	// the direction of the paragraph the line belongs to
+	pub rtl: bool,
+	justify: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Caret {
+	pub line: usize,
+	// how far along the line the caret is; a y coordinate for vertical lines
+	pub offset: f32,
+}
+
+struct ClusterBox {
+	text: Range<usize>,
+	start: f32,
+	end: f32,
+	rtl: bool,
+}
+impl ClusterBox {
+	// the point a fraction of the way through the cluster, in reading order
+	fn at(&self, fraction: f32) -> f32 {
+		if self.rtl {
+			self.end - (self.end - self.start) * fraction
+		} else {
+			self.start + (self.end - self.start) * fraction
+		}
+	}
+}
+
+// in pixels, like the layout they were measured from
+#[derive(Debug, Clone)]
+pub struct TextMetrics {
+	// the length of the longest line, without trailing whitespace
+	pub advance: f32,
+	// the corners of the box the glyph outlines cover, or none if nothing is drawn
+	pub ink: Option<(Vector2<f32>, Vector2<f32>)>,
+	// the largest ascent, descent and line gap of any line, with the descent negative
+	pub ascent: f32,
+	pub descent: f32,
+	pub line_gap: f32,
+	pub lines: Vec<LineMetrics>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LineMetrics {
+	pub text: Range<usize>,
+	pub start: f32,
+	pub baseline: f32,
+	pub advance: f32,
+	pub ink: Option<(Vector2<f32>, Vector2<f32>)>,
+	pub ascent: f32,
+	pub descent: f32,
+	pub line_gap: f32,
+}
+
+#[derive(Clone)]
+struct ShapedGlyph {
+	id: u32,
+	cluster: usize,
+	span: usize,
+	advance: Vector2<f32>,
+	offset: Vector2<f32>,
+	space: bool,
+	rtl: bool,
+	trailing: bool,
+}
+
+struct Paragraph<'a> {
+	fonts: &'a Fonts,
+	text: &'a str,
+	spans: &'a [(Range<usize>, TextStyle)],
+	options: &'a LayoutOptions,
+	max_length: Option<f32>,
+}
+impl<'a> Paragraph<'a> {
+	fn layout(&self, range: Range<usize>, allowed: &[usize]) -> (Vec<ShapedGlyph>, Vec<Line>) {
+		let Self { fonts, text, spans, options, max_length } = *self;
+		let vertical = options.direction == Direction::Vertical;
+		let content = text[range.clone()].trim_end_matches(is_line_terminator);
+		let bidi = BidiInfo::new(content, None);
+		let rtl = bidi.paragraphs.first().map_or(false, |paragraph| paragraph.level.is_rtl());
+
+		// shape each run of the same embedding level, script and span on its own, but keep the glyphs in logical
+		// order until the lines are known
+		let mut runs = vec![];
+		let mut run_start = 0;
+		let mut run_script = None;
+		for (i, ch) in content.char_indices() {
+			let script = script_of(ch);
+			let new_script = matches!((run_script, script), (Some(a), Some(b)) if a != b);
+			let new_span = self.span_at(range.start + i) != self.span_at(range.start + run_start);
+			if i > run_start && (bidi.levels[i] != bidi.levels[run_start] || new_script || new_span) {
+				runs.push((run_start..i, run_script));
+				run_start = i;
+				run_script = None;
+			}
+			run_script = run_script.or(script);
+		}
+		if !content.is_empty() {
+			runs.push((run_start..content.len(), run_script));
+		}
+
+		// clusters are byte indices into the paragraph
+		let features: Vec<_> = options
+			.features
+			.iter()
+			.filter(|feature| feature.range.start < range.end && feature.range.end > range.start)
+			.map(|feature| {
+				let start = feature.range.start.saturating_sub(range.start);
+				let end = feature.range.end.min(range.end) - range.start;
+				HFeature::new(feature.tag, feature.value, start..end)
+			})
+			.collect();
+
+		let mut shaped = vec![];
+		for (run, script) in runs {
+			let rtl = !vertical && bidi.levels[run.start].is_rtl();
+			let direction = if vertical {
+				HDirection::Ttb
+			} else if rtl {
+				HDirection::Rtl
+			} else {
+				HDirection::Ltr
+			};
+			let span = self.span_at(range.start + run.start);
+			let style = &spans[span].1;
+			let font = fonts.get(style.font);
+			let scale = style.size / font.metrics.units_per_em as f32;
+
+			let mut buffer = UnicodeBuffer::new().add_str_item(content, &content[run]).set_direction(direction);
+			if let Some(script) = style.script.or(script) {
+				buffer = buffer.set_script(script);
+			}
+			if let Some(language) = style.language.as_ref().and_then(|language| language.parse::<Language>().ok()) {
+				buffer = buffer.set_language(language);
+			}
+			let output = shape(&font.hfont, buffer, &features);
+
+			let run_glyphs = shaped.len();
+			for (info, position) in output.get_glyph_infos().iter().zip(output.get_glyph_positions()) {
+				let cluster = range.start + info.cluster as usize;
+				// harfbuzz's y axis points up
+				shaped.push(ShapedGlyph {
+					id: info.codepoint,
+					cluster,
+					span,
+					advance: Vector2::new(position.x_advance as f32, -position.y_advance as f32) * scale,
+					offset: Vector2::new(position.x_offset as f32, -position.y_offset as f32) * scale,
+					space: text[cluster..].chars().next().map_or(false, char::is_whitespace),
+					rtl,
+					trailing: false,
+				});
+			}
+			// harfbuzz returns right-to-left runs in visual order
+			if rtl {
+				shaped[run_glyphs..].reverse();
+			}
+		}
+
+		let glyph_at = |i: usize| shaped.partition_point(|glyph| glyph.cluster < i);
+		let visible_end = |start: usize, mut end: usize| {
+			while end > start && shaped[end - 1].space {
+				end -= 1;
+			}
+			end
+		};
+		let advance = |glyph: &ShapedGlyph| if vertical { glyph.advance.y } else { glyph.advance.x };
+		let length = |start: usize, end: usize| shaped[start..end].iter().map(advance).sum::<f32>();
+
+		let mut lines = vec![];
+		let mut start = 0;
+		let mut breaks = allowed.iter().map(|&i| glyph_at(i)).peekable();
+		loop {
+			let mut end = shaped.len();
+			if let Some(max_length) = max_length {
+				if length(start, visible_end(start, end)) > max_length {
+					// take the last break that fits, or the first one if none do
+					let mut fit = None;
+					while let Some(&i) = breaks.peek() {
+						if i <= start {
+							breaks.next();
+						} else if fit.is_none() || length(start, visible_end(start, i)) <= max_length {
+							fit = Some(i);
+							breaks.next();
+						} else {
+							break;
+						}
+					}
+					end = fit.unwrap_or(end);
+				}
+			}
+
+			let text_start = if start == 0 { range.start } else { shaped[start].cluster };
+			let text_end = if end == shaped.len() { range.end } else { shaped[end].cluster };
+			// an empty line takes its height from the span it's in
+			let line_spans = if start == end {
+				vec![self.span_at(text_start)]
+			} else {
+				shaped[start..end].iter().map(|glyph| glyph.span).collect()
+			};
+			let (mut ascent, mut descent, mut line_gap) = (0f32, 0f32, 0f32);
+			for span in line_spans {
+				let extents = self.extents(span);
+				ascent = ascent.max(extents.0);
+				descent = descent.min(extents.1);
+				line_gap = line_gap.max(extents.2);
+			}
+
+			lines.push(Line {
+				text: text_start..text_end,
+				glyphs: start..end,
+				start: 0.0,
+				baseline: 0.0,
+				length: length(start, visible_end(start, end)),
+				ascent,
+				descent,
+				rtl,
+				line_gap,
+				justify: true,
+			});
+
+			start = end;
+			if start == shaped.len() {
+				break;
+			}
+		}
+
+		// reorder each line's glyphs visually
+		let mut ordered = Vec::with_capacity(shaped.len());
+		for line in &lines {
+			let visible = visible_end(line.glyphs.start, line.glyphs.end);
+			let mut push = |i: usize| ordered.push(ShapedGlyph { trailing: i >= visible, ..shaped[i].clone() });
+
+			let line_text = line.text.start - range.start..(line.text.end - range.start).min(content.len());
+			match bidi.paragraphs.first() {
+				Some(paragraph) if !vertical && !line_text.is_empty() => {
+					let (levels, runs) = bidi.visual_runs(paragraph, line_text);
+					for run in runs {
+						let glyphs = glyph_at(range.start + run.start)..glyph_at(range.start + run.end);
+						if levels[run.start].is_rtl() {
+							glyphs.rev().for_each(&mut push);
+						} else {
+							glyphs.for_each(&mut push);
+						}
+					}
+				},
+				_ => line.glyphs.clone().for_each(push),
+			}
+		}
+
+		(ordered, lines)
+	}
+
+	// the span containing a byte index, or the last one at the end of the text
+	fn span_at(&self, i: usize) -> usize {
+		self.spans.partition_point(|(range, _)| range.end <= i).min(self.spans.len() - 1)
+	}
+
+	// ascent, descent and line gap in pixels
+	fn extents(&self, span: usize) -> (f32, f32, f32) {
+		let style = &self.spans[span].1;
+		if self.options.direction == Direction::Vertical {
+			return (style.size / 2.0, -style.size / 2.0, 0.0);
+		}
+
+		let metrics = &self.fonts.get(style.font).metrics;
+		let scale = style.size / metrics.units_per_em as f32;
+		(metrics.ascent * scale, metrics.descent * scale, metrics.line_gap * scale)
+	}
+}
+
+// returns none for characters that take the script of the text around them
+fn script_of(ch: char) -> Option<Tag> {
+	let script = unsafe { hb::hb_unicode_script(hb::hb_unicode_funcs_get_default(), ch as u32) };
+	let [a, b, c, d] = script.to_be_bytes();
+	match &[a, b, c, d] {
+		b"Zyyy" | b"Zinh" | b"Zzzz" => None,
+		_ => Some(Tag::new(a as char, b as char, c as char, d as char)),
+	}
+}
+
+fn is_line_terminator(ch: char) -> bool {
+	matches!(ch, '\n' | '\u{b}' | '\u{c}' | '\r' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+// removes a range from a list of ranges, splitting any that it's in the middle of
+fn cut(ranges: Vec<(f32, f32)>, (low, high): (f32, f32)) -> Vec<(f32, f32)> {
+	let mut cut = Vec::with_capacity(ranges.len() + 1);
+	for (start, end) in ranges {
+		if start < low.min(end) {
+			cut.push((start, low.min(end)));
+		}
+		if high.max(start) < end {
+			cut.push((high.max(start), end));
+		}
+	}
+	cut
+}
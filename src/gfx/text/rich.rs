@@ -0,0 +1,101 @@
+use crate::gfx::text::{FontId, Renderer};
+use harfbuzz_rs::Tag;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichText {
+	pub spans: Vec<Span>,
+}
+impl RichText {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn plain(text: impl Into<String>, style: TextStyle) -> Self {
+		Self::new().push(text, style)
+	}
+
+	pub fn push(mut self, text: impl Into<String>, style: TextStyle) -> Self {
+		self.spans.push(Span { text: text.into(), style });
+		self
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+	pub text: String,
+	pub style: TextStyle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStyle {
+	pub font: FontId,
+	// pixels per em
+	pub size: f32,
+	pub color: [f32; 4],
+	pub underline: bool,
+	pub strikethrough: bool,
+	pub overline: bool,
+	// whether underlines break around descenders that cross them
+	pub skip_ink: bool,
+	// the CPAL palette that color glyphs are drawn with
+	pub palette: usize,
+	// detected for each run when not set
+	pub script: Option<Tag>,
+	// a BCP 47 tag, like "sr" or "ru"; detected from the environment when not set
+	pub language: Option<String>,
+	// the font's own renderer when not set
+	pub renderer: Option<Renderer>,
+}
+impl TextStyle {
+	pub fn new(font: FontId, size: f32) -> Self {
+		Self {
+			font,
+			size,
+			color: [1.0, 1.0, 1.0, 1.0],
+			underline: false,
+			strikethrough: false,
+			overline: false,
+			skip_ink: true,
+			palette: 0,
+			script: None,
+			language: None,
+			renderer: None,
+		}
+	}
+
+	pub fn color(self, color: [f32; 4]) -> Self {
+		Self { color, ..self }
+	}
+
+	pub fn underline(self, underline: bool) -> Self {
+		Self { underline, ..self }
+	}
+
+	pub fn strikethrough(self, strikethrough: bool) -> Self {
+		Self { strikethrough, ..self }
+	}
+
+	pub fn overline(self, overline: bool) -> Self {
+		Self { overline, ..self }
+	}
+
+	pub fn skip_ink(self, skip_ink: bool) -> Self {
+		Self { skip_ink, ..self }
+	}
+
+	pub fn palette(self, palette: usize) -> Self {
+		Self { palette, ..self }
+	}
+
+	pub fn script(self, script: Option<Tag>) -> Self {
+		Self { script, ..self }
+	}
+
+	pub fn language(self, language: Option<String>) -> Self {
+		Self { language, ..self }
+	}
+
+	pub fn renderer(self, renderer: Option<Renderer>) -> Self {
+		Self { renderer, ..self }
+	}
+}
@@ -0,0 +1,131 @@
+use crate::gfx::text::{cache::DistanceFields, cubic_point, Outliner, Variation};
+use font_kit::{error::FontLoadingError, outline::OutlineSink};
+use image::{GrayImage, Luma};
+use nalgebra::Vector2;
+use pathfinder_geometry::{line_segment::LineSegment2F, vector::Vector2F};
+
+// the size distance fields are made at. edges stay sharp far past it, but corners get rounder.
+const SDF_PPEM: f32 = 32.0;
+// how far from the outline distances are stored, in field pixels
+const SDF_RANGE: f32 = 4.0;
+// curves are flattened into this many lines
+const CURVE_STEPS: u32 = 8;
+
+pub(super) fn distance_fields(
+	data: &[u8],
+	index: u32,
+	variations: &[Variation],
+	glyphs: &[u32],
+) -> Result<DistanceFields, FontLoadingError> {
+	// distance fields are meant to be scaled and transformed, so hinting them for one size wouldn't help
+	let outliner = Outliner::new(data, index, variations, None)?;
+	let scale = SDF_PPEM / outliner.units_per_em() as f32;
+	let fields = glyphs
+		.iter()
+		.filter_map(|&glyph| {
+			let mut sink = SegmentSink::new(scale);
+			if !outliner.outline(glyph, &mut sink) {
+				return None;
+			}
+			let (image, offset) = distance_field(&sink.segments)?;
+			Some((glyph, image, offset, SDF_PPEM))
+		})
+		.collect();
+	Ok(DistanceFields { fields })
+}
+
+// returns the field, and its top left corner relative to the glyph's origin with y pointing down. the outline is at
+// 0.5, with the inside above it.
+fn distance_field(segments: &[(Vector2F, Vector2F)]) -> Option<(GrayImage, Vector2<f32>)> {
+	let (mut min, mut max) = segments.first().map(|&(a, _)| (a, a))?;
+	for &(a, b) in segments {
+		min = min.min(a).min(b);
+		max = max.max(a).max(b);
+	}
+
+	let left = (min.x() - SDF_RANGE).floor();
+	let bottom = (min.y() - SDF_RANGE).floor();
+	let right = (max.x() + SDF_RANGE).ceil();
+	let top = (max.y() + SDF_RANGE).ceil();
+
+	let image = GrayImage::from_fn((right - left) as u32, (top - bottom) as u32, |x, y| {
+		// pixel centers, with y pointing up like the outline
+		let point = Vector2F::new(left + x as f32 + 0.5, top - y as f32 - 0.5);
+
+		let mut distance = f32::MAX;
+		let mut winding = 0;
+		for &(a, b) in segments {
+			distance = distance.min(segment_distance(point, a, b));
+			if (a.y() <= point.y()) != (b.y() <= point.y()) {
+				let t = (point.y() - a.y()) / (b.y() - a.y());
+				if a.x() + (b.x() - a.x()) * t > point.x() {
+					winding += if b.y() > a.y() { 1 } else { -1 };
+				}
+			}
+		}
+
+		let distance = if winding != 0 { distance } else { -distance };
+		Luma([((0.5 + distance / (SDF_RANGE * 2.0)).clamp(0.0, 1.0) * 255.0).round() as u8])
+	});
+
+	Some((image, Vector2::new(left, -top)))
+}
+
+fn segment_distance(point: Vector2F, a: Vector2F, b: Vector2F) -> f32 {
+	let ab = b - a;
+	if ab.square_length() == 0.0 {
+		return (point - a).length();
+	}
+	let t = ((point - a).dot(ab) / ab.square_length()).clamp(0.0, 1.0);
+	(a + ab * t - point).length()
+}
+
+// flattens an outline into lines, scaled to field pixels
+struct SegmentSink {
+	scale: f32,
+	pen: Vector2F,
+	start: Vector2F,
+	segments: Vec<(Vector2F, Vector2F)>,
+}
+impl SegmentSink {
+	fn new(scale: f32) -> Self {
+		Self { scale, pen: Vector2F::zero(), start: Vector2F::zero(), segments: vec![] }
+	}
+
+	fn push(&mut self, to: Vector2F) {
+		self.segments.push((self.pen, to));
+		self.pen = to;
+	}
+}
+impl OutlineSink for SegmentSink {
+	fn move_to(&mut self, to: Vector2F) {
+		self.pen = to * self.scale;
+		self.start = self.pen;
+	}
+
+	fn line_to(&mut self, to: Vector2F) {
+		self.push(to * self.scale);
+	}
+
+	fn quadratic_curve_to(&mut self, ctrl: Vector2F, to: Vector2F) {
+		let (from, ctrl, to) = (self.pen, ctrl * self.scale, to * self.scale);
+		for i in 1..=CURVE_STEPS {
+			let t = i as f32 / CURVE_STEPS as f32;
+			let u = 1.0 - t;
+			self.push(from * (u * u) + ctrl * (2.0 * u * t) + to * (t * t));
+		}
+	}
+
+	fn cubic_curve_to(&mut self, ctrl: LineSegment2F, to: Vector2F) {
+		let cubic = [self.pen, ctrl.from() * self.scale, ctrl.to() * self.scale, to * self.scale];
+		for i in 1..=CURVE_STEPS {
+			self.push(cubic_point(&cubic, i as f32 / CURVE_STEPS as f32));
+		}
+	}
+
+	fn close(&mut self) {
+		if self.pen != self.start {
+			self.push(self.start);
+		}
+	}
+}
@@ -5,12 +5,13 @@ use crate::gfx::{
 	camera::Camera,
 	model::Mesh,
 	render::{render, RenderWindowState},
+	text::{field::TextField, Anchor, FillRule, Font, Fonts, Label, Renderer, Text},
 	window::Window,
 };
 use examples::{INDICES, NORMALS, VERTICES};
 use gfx::Gfx;
-use nalgebra::{Quaternion, Vector3};
-use shipyard::{Get, ViewMut, Workload, World};
+use nalgebra::{Quaternion, Vector2, Vector3};
+use shipyard::{Get, IntoIter, UniqueView, ViewMut, Workload, World};
 use std::collections::HashMap;
 use vulkano::sync::GpuFuture;
 use winit::{
@@ -25,6 +26,15 @@ fn main() {
 	let window = Window::new(&mut gfx, &event_loop);
 	let render_window_state = RenderWindowState::new(&mut gfx, &window);
 
+	let queue = window.device_ctx().queue();
+	let mut fonts = Fonts::new(queue);
+	let roboto = fonts.add(Font::from_path(queue, "res/Roboto-Regular.ttf", 0).unwrap());
+	// the same face again, filled with even-odd, to check how it mixes with nonzero text
+	let roboto_even_odd =
+		fonts.add(Font::from_path(queue, "res/Roboto-Regular.ttf", 0).unwrap().fill_rule(FillRule::EvenOdd));
+	let font_future =
+		fonts.get_mut(roboto).load_block("Basic Latin").join(fonts.get_mut(roboto_even_odd).load_block("Basic Latin"));
+
 	let (mesh, mesh_future) =
 		Mesh::new(&window, VERTICES.iter().cloned(), NORMALS.iter().cloned(), INDICES.iter().cloned());
 
@@ -35,14 +45,51 @@ fn main() {
 	let mut windows = HashMap::new();
 
 	let mut world = World::new();
+	let mut field = TextField::new(&fonts, "Edit me", roboto, 20.0, true);
+	field.position = Vector2::new(20.0, 60.0);
+	field.focused = true;
+
 	world.add_unique(gfx).unwrap();
+	world.add_unique(fonts).unwrap();
+	world.add_entity((field,));
 	world.add_entity((mesh,));
-	world.add_entity(("The quick brown fox jumps over the lazy dog.",));
+	let mut label = Label::new("Teapot", roboto, 12.0);
+	label.position = Vector3::new(0.0, 45.0, 0.0);
+	world.add_entity((label,));
+	let mut label = Label::new("Planar", roboto, 12.0);
+	label.position = Vector3::new(0.0, 0.0, -45.0);
+	label.rotation = Some(Quaternion::identity());
+	// seen at an angle, so it's drawn with distance fields, while the font's other text keeps its windings
+	label.renderer = Some(Renderer::Sdf);
+	world.add_entity((label,));
+	let mut text = Text::new("The quick brown fox jumps over the lazy dog.", roboto, 16.0);
+	text.position = Vector2::new(20.0, 20.0);
+	world.add_entity((text,));
+	// the line crosses every stem, so it shows whether decorations and glyphs cover each other properly
+	let mut text = Text::new("Hillbilly minimum", roboto, 32.0);
+	text.position = Vector2::new(20.0, 100.0);
+	text.strikethrough = true;
+	world.add_entity((text,));
+	// nonzero and even-odd text drawn over each other in one pass. the nonzero glyphs should stay solid where they
+	// overlap.
+	let mut text = Text::new("Overlap", roboto, 48.0);
+	text.position = Vector2::new(20.0, 150.0);
+	world.add_entity((text,));
+	let mut text = Text::new("Overlap", roboto_even_odd, 48.0);
+	text.position = Vector2::new(30.0, 160.0);
+	text.color = [1.0, 0.6, 0.2, 1.0];
+	world.add_entity((text,));
+	let mut text = Text::new("Bottom right", roboto, 24.0);
+	text.position = Vector2::new(size.width as f32 - 20.0, size.height as f32 - 20.0);
+	text.anchor = Anchor::BottomRight;
+	text.color = [0.1, 0.1, 0.1, 1.0];
+	text.background = Some([0.9, 0.9, 0.8, 1.0]);
+	world.add_entity((text,));
 	windows.insert(window.id(), world.add_entity((window, render_window_state, camera)));
 
 	Workload::builder("default").with_system(&render).add_to_world(&world).unwrap();
 
-	mesh_future.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+	mesh_future.join(font_future).then_signal_fence_and_flush().unwrap().wait(None).unwrap();
 
 	event_loop.run(move |event, _, control_flow| match event {
 		Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => *control_flow = ControlFlow::Exit,
@@ -56,6 +103,13 @@ fn main() {
 				})
 				.unwrap();
 		},
+		Event::WindowEvent { event, .. } => world
+			.run(|fonts: UniqueView<Fonts>, mut fields: ViewMut<TextField>| {
+				for mut field in (&mut fields).iter() {
+					field.handle_event(&fonts, &event);
+				}
+			})
+			.unwrap(),
 		Event::RedrawEventsCleared => world.run_default().unwrap(),
 		_ => (),
 	});